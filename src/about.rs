@@ -42,6 +42,8 @@ mod imp {
         pub yubikey_enrollment: TemplateChild<Label>,
         #[template_child]
         pub device_id: TemplateChild<Label>,
+        #[template_child]
+        pub certificates: TemplateChild<Label>,
         pub(super) refresh_cancel: RefCell<Option<CancelGuard>>,
     }
 
@@ -115,6 +117,7 @@ impl AboutPage {
         self.imp().device_id.set_label("loading...");
 
         if let Some(app) = self.get_app_ref() {
+            self.imp().certificates.set_label(&app.certificate_status());
             let cancellable = gio::Cancellable::new();
             self.imp()
                 .refresh_cancel
@@ -150,6 +153,7 @@ impl AboutPage {
                 .set_markup(format_optional_bool_status(None));
             self.imp().yubikey_enrollment.set_label("unknown");
             self.imp().device_id.set_label("unknown");
+            self.imp().certificates.set_label("unknown");
         }
     }
 
@@ -0,0 +1,415 @@
+pub use givc_client::client::StatsResponse;
+pub use givc_common::pb::admin::StartResponse;
+
+use anyhow::bail;
+use futures::future::LocalBoxFuture;
+use futures::stream::{self, BoxStream, StreamExt};
+use givc_client::AdminClient;
+use givc_client::endpoint::TlsConfig;
+use givc_common::address::EndpointAddress;
+use givc_common::pb::admin::admin_service_client::AdminServiceClient;
+use givc_common::pb::admin::{
+    ApplicationRequest, Empty, StartServiceRequest, StartVmRequest, StatsRequest, WatchItem,
+    watch_item,
+};
+use givc_common::pb::locale::{LocaleMessage, TimezoneMessage};
+use givc_common::query::{Event, QueryResult};
+use std::sync::Arc;
+use tonic::transport::Channel;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone)]
+pub struct HostSysinfoStatus {
+    pub ghaf_version: String,
+    pub secure_boot: Option<bool>,
+    pub disk_encryption: Option<bool>,
+}
+
+/// Units known when the watch started, and the events that follow.
+pub struct Watch {
+    pub initial: Vec<QueryResult>,
+    pub events: BoxStream<'static, Event>,
+}
+
+pub type BackendFuture<'a, T> = LocalBoxFuture<'a, Result<T, anyhow::Error>>;
+
+/// Every call the control panel makes to the admin service.
+///
+/// `AdminClient` is the real thing. The `mock` build talks to an in-process `FakeService`
+/// instead, see `fake_connector`.
+pub trait AdminBackend {
+    fn watch(&self) -> BackendFuture<'_, Watch>;
+    fn start_vm(&self, vm: String) -> BackendFuture<'_, StartResponse>;
+    fn start_app(
+        &self,
+        app: String,
+        vm: String,
+        args: Vec<String>,
+    ) -> BackendFuture<'_, StartResponse>;
+    fn start_service(&self, service: String, vm: String) -> BackendFuture<'_, StartResponse>;
+    fn stop(&self, name: String) -> BackendFuture<'_, ()>;
+    fn pause(&self, name: String) -> BackendFuture<'_, ()>;
+    fn resume(&self, name: String) -> BackendFuture<'_, ()>;
+    fn set_locale(&self, locale: String) -> BackendFuture<'_, ()>;
+    fn set_timezone(&self, timezone: String) -> BackendFuture<'_, ()>;
+    fn get_stats(&self, vm: String) -> BackendFuture<'_, StatsResponse>;
+    fn sysinfo(&self) -> BackendFuture<'_, HostSysinfoStatus>;
+}
+
+/// Creates the backend for a connection to `address`. Called on the thread that runs the
+/// backend, inside its tokio runtime.
+pub type Connector = Arc<
+    dyn Fn(EndpointAddress, Option<(String, TlsConfig)>) -> Box<dyn AdminBackend> + Send + Sync,
+>;
+
+pub fn givc_connector() -> Connector {
+    Arc::new(
+        |address: EndpointAddress,
+         tls_info: Option<(String, TlsConfig)>|
+         -> Box<dyn AdminBackend> {
+            Box::new(AdminClient::from_endpoint_address(address, tls_info))
+        },
+    )
+}
+
+/// Translate a unit change into the event the client delivers for it.
+fn watch_event(status: watch_item::Status) -> Result<Option<Event>, anyhow::Error> {
+    use watch_item::Status;
+
+    Ok(match status {
+        Status::Initial(_) => None,
+        Status::Added(item) => Some(Event::UnitRegistered(QueryResult::try_from(item)?)),
+        Status::Updated(item) => Some(Event::UnitStatusChanged(QueryResult::try_from(item)?)),
+        Status::Removed(item) => Some(Event::UnitShutdown(QueryResult::try_from(item)?)),
+    })
+}
+
+/// The admin service API over a channel made by the caller, for connections the givc
+/// client cannot set up, such as those that check a pinned certificate.
+pub struct ChannelClient(AdminServiceClient<Channel>);
+
+impl ChannelClient {
+    pub fn new(channel: Channel) -> Self {
+        Self(AdminServiceClient::new(channel))
+    }
+}
+
+fn app_request(app_name: String, vm_name: Option<String>, args: Vec<String>) -> ApplicationRequest {
+    ApplicationRequest {
+        app_name,
+        vm_name,
+        args,
+    }
+}
+
+impl AdminBackend for ChannelClient {
+    fn watch(&self) -> BackendFuture<'_, Watch> {
+        Box::pin(async move {
+            let mut items = self.0.clone().watch(Empty {}).await?.into_inner();
+            let Some(WatchItem {
+                status: Some(watch_item::Status::Initial(list)),
+            }) = items.message().await?
+            else {
+                bail!("The admin service did not start the watch with the list of units");
+            };
+            let initial = list
+                .list
+                .into_iter()
+                .map(QueryResult::try_from)
+                .collect::<Result<_, _>>()?;
+            let events = stream::unfold(items, |mut items| async move {
+                match items.message().await {
+                    Ok(Some(item)) => Some((item.status, items)),
+                    Ok(None) => None,
+                    Err(e) => {
+                        warn!("Watch stream failed: {e}");
+                        None
+                    }
+                }
+            })
+            .filter_map(|status| async move {
+                watch_event(status?)
+                    .inspect_err(|e| warn!("Dropping watch event: {e}"))
+                    .ok()
+                    .flatten()
+            });
+            Ok(Watch {
+                initial,
+                events: events.boxed(),
+            })
+        })
+    }
+
+    fn start_vm(&self, vm: String) -> BackendFuture<'_, StartResponse> {
+        Box::pin(async move {
+            let request = StartVmRequest { vm_name: vm };
+            Ok(self.0.clone().start_vm(request).await?.into_inner())
+        })
+    }
+
+    fn start_app(
+        &self,
+        app: String,
+        vm: String,
+        args: Vec<String>,
+    ) -> BackendFuture<'_, StartResponse> {
+        Box::pin(async move {
+            let request = app_request(app, Some(vm), args);
+            let response = self.0.clone().start_application(request).await?;
+            Ok(response.into_inner())
+        })
+    }
+
+    fn start_service(&self, service: String, vm: String) -> BackendFuture<'_, StartResponse> {
+        Box::pin(async move {
+            let request = StartServiceRequest {
+                service_name: service,
+                vm_name: vm,
+            };
+            Ok(self.0.clone().start_service(request).await?.into_inner())
+        })
+    }
+
+    fn stop(&self, name: String) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            let request = app_request(name, None, Vec::new());
+            self.0.clone().stop_application(request).await?;
+            Ok(())
+        })
+    }
+
+    fn pause(&self, name: String) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            let request = app_request(name, None, Vec::new());
+            self.0.clone().pause_application(request).await?;
+            Ok(())
+        })
+    }
+
+    fn resume(&self, name: String) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            let request = app_request(name, None, Vec::new());
+            self.0.clone().resume_application(request).await?;
+            Ok(())
+        })
+    }
+
+    fn set_locale(&self, locale: String) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            self.0.clone().set_locale(LocaleMessage { locale }).await?;
+            Ok(())
+        })
+    }
+
+    fn set_timezone(&self, timezone: String) -> BackendFuture<'_, ()> {
+        Box::pin(async move {
+            self.0
+                .clone()
+                .set_timezone(TimezoneMessage { timezone })
+                .await?;
+            Ok(())
+        })
+    }
+
+    fn get_stats(&self, vm: String) -> BackendFuture<'_, StatsResponse> {
+        Box::pin(async move {
+            let request = StatsRequest { vm_name: vm };
+            Ok(self.0.clone().get_stats(request).await?.into_inner())
+        })
+    }
+
+    fn sysinfo(&self) -> BackendFuture<'_, HostSysinfoStatus> {
+        Box::pin(async move {
+            let status = self.0.clone().get_sysinfo(Empty {}).await?.into_inner();
+            Ok(HostSysinfoStatus {
+                ghaf_version: status.ghaf_version,
+                secure_boot: status.secure_boot,
+                disk_encryption: status.disk_encrypted,
+            })
+        })
+    }
+}
+
+impl AdminBackend for AdminClient {
+    fn watch(&self) -> BackendFuture<'_, Watch> {
+        Box::pin(async move {
+            let result = AdminClient::watch(self).await?;
+            Ok(Watch {
+                initial: result.initial,
+                events: result.channel.boxed(),
+            })
+        })
+    }
+
+    fn start_vm(&self, vm: String) -> BackendFuture<'_, StartResponse> {
+        Box::pin(AdminClient::start_vm(self, vm))
+    }
+
+    fn start_app(
+        &self,
+        app: String,
+        vm: String,
+        args: Vec<String>,
+    ) -> BackendFuture<'_, StartResponse> {
+        Box::pin(AdminClient::start_app(self, app, vm, args))
+    }
+
+    fn start_service(&self, service: String, vm: String) -> BackendFuture<'_, StartResponse> {
+        Box::pin(AdminClient::start_service(self, service, vm))
+    }
+
+    fn stop(&self, name: String) -> BackendFuture<'_, ()> {
+        Box::pin(AdminClient::stop(self, name))
+    }
+
+    fn pause(&self, name: String) -> BackendFuture<'_, ()> {
+        Box::pin(AdminClient::pause(self, name))
+    }
+
+    fn resume(&self, name: String) -> BackendFuture<'_, ()> {
+        Box::pin(AdminClient::resume(self, name))
+    }
+
+    fn set_locale(&self, locale: String) -> BackendFuture<'_, ()> {
+        Box::pin(AdminClient::set_locale(self, locale))
+    }
+
+    fn set_timezone(&self, timezone: String) -> BackendFuture<'_, ()> {
+        Box::pin(AdminClient::set_timezone(self, timezone))
+    }
+
+    fn get_stats(&self, vm: String) -> BackendFuture<'_, StatsResponse> {
+        Box::pin(AdminClient::get_stats(self, vm))
+    }
+
+    fn sysinfo(&self) -> BackendFuture<'_, HostSysinfoStatus> {
+        Box::pin(async move {
+            let status = AdminClient::sysinfo(self).await?;
+            Ok(HostSysinfoStatus {
+                ghaf_version: status.ghaf_version,
+                secure_boot: status.secure_boot,
+                disk_encryption: status.disk_encrypted,
+            })
+        })
+    }
+}
+
+#[cfg(feature = "mock")]
+pub use self::fake::fake_connector;
+
+#[cfg(feature = "mock")]
+mod fake {
+    use futures::stream::StreamExt;
+    use givc_client::endpoint::TlsConfig;
+    use givc_common::address::EndpointAddress;
+    use std::sync::Arc;
+
+    use super::{
+        AdminBackend, BackendFuture, Connector, HostSysinfoStatus, StartResponse, StatsResponse,
+        Watch, watch_event,
+    };
+    use crate::fake_service::{FakeService, GHAF_VERSION};
+    use crate::prelude::*;
+    use crate::scenario::{Scenario, Unit};
+
+    /// Serve `scenario` from a `FakeService` in the backend's runtime. The scenario starts
+    /// over on every connection.
+    pub fn fake_connector(scenario: Scenario) -> Connector {
+        Arc::new(
+            move |_: EndpointAddress, _: Option<(String, TlsConfig)>| -> Box<dyn AdminBackend> {
+                let service = Arc::new(FakeService::new(scenario.clone()));
+                tokio::spawn(Arc::clone(&service).play_events());
+                Box::new(service)
+            },
+        )
+    }
+
+    impl AdminBackend for Arc<FakeService> {
+        fn watch(&self) -> BackendFuture<'_, Watch> {
+            Box::pin(async move {
+                let (units, changes) = FakeService::subscribe(self);
+                let initial = units
+                    .iter()
+                    .map(Unit::to_query_result)
+                    .collect::<Result<_, _>>()?;
+                let events = changes.filter_map(|status| async move {
+                    watch_event(status)
+                        .inspect_err(|e| warn!("Dropping fake service event: {e}"))
+                        .ok()
+                        .flatten()
+                });
+                Ok(Watch {
+                    initial,
+                    events: events.boxed(),
+                })
+            })
+        }
+
+        fn start_vm(&self, vm: String) -> BackendFuture<'_, StartResponse> {
+            Box::pin(async move {
+                FakeService::start_vm(self, &vm)?;
+                Ok(StartResponse::default())
+            })
+        }
+
+        fn start_app(
+            &self,
+            app: String,
+            vm: String,
+            args: Vec<String>,
+        ) -> BackendFuture<'_, StartResponse> {
+            Box::pin(async move {
+                FakeService::start_app(self, &app, &vm, &args)?;
+                Ok(StartResponse::default())
+            })
+        }
+
+        fn start_service(&self, service: String, _vm: String) -> BackendFuture<'_, StartResponse> {
+            Box::pin(async move {
+                FakeService::start_service(self, &service)?;
+                Ok(StartResponse::default())
+            })
+        }
+
+        fn stop(&self, name: String) -> BackendFuture<'_, ()> {
+            Box::pin(async move { Ok(FakeService::stop(self, &name)?) })
+        }
+
+        fn pause(&self, name: String) -> BackendFuture<'_, ()> {
+            Box::pin(async move { Ok(FakeService::pause(self, &name)?) })
+        }
+
+        fn resume(&self, name: String) -> BackendFuture<'_, ()> {
+            Box::pin(async move { Ok(FakeService::resume(self, &name)?) })
+        }
+
+        fn set_locale(&self, locale: String) -> BackendFuture<'_, ()> {
+            Box::pin(async move {
+                info!("SetLocale {locale}");
+                Ok(())
+            })
+        }
+
+        fn set_timezone(&self, timezone: String) -> BackendFuture<'_, ()> {
+            Box::pin(async move {
+                info!("SetTimezone {timezone}");
+                Ok(())
+            })
+        }
+
+        fn get_stats(&self, vm: String) -> BackendFuture<'_, StatsResponse> {
+            Box::pin(async move { Ok(FakeService::stats(self, &vm)?) })
+        }
+
+        fn sysinfo(&self) -> BackendFuture<'_, HostSysinfoStatus> {
+            Box::pin(async {
+                Ok(HostSysinfoStatus {
+                    ghaf_version: String::from(GHAF_VERSION),
+                    secure_boot: Some(false),
+                    disk_encryption: Some(false),
+                })
+            })
+        }
+    }
+}
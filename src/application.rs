@@ -1,21 +1,24 @@
 use adw::subclass::prelude::*;
-use gio::ListModel;
 use gtk::prelude::*;
 use gtk::{gio, glib};
 
+use crate::audit_log::{self, AuditEntry, Origin};
+use crate::connection_profiles::{ConnectionProfile, ConnectionProfiles, PinnedCertificate};
 use crate::control_action::ControlAction;
 use crate::data_gobject::DataGObject;
 use crate::error_popup::ErrorPopup;
+use crate::launch_args::Destination;
 use crate::plot::Plot;
+use crate::preferences::Preferences;
 use crate::security_icon::SecurityIcon;
 use crate::serie::Serie;
 use crate::service_gobject::ServiceGObject;
+use crate::service_model::ServiceModel;
 pub use crate::service_model::StatsResponse;
 pub use crate::service_model::HostSysinfoStatus;
 use crate::settings_action::SettingsAction;
 use crate::status_icon::StatusIcon;
-use givc_client::endpoint::TlsConfig;
-use log::debug;
+use log::{debug, info, warn};
 
 mod imp {
     use adw::{prelude::*, subclass::prelude::*};
@@ -23,12 +26,19 @@ mod imp {
     use glib::Properties;
     use gtk::CssProvider;
     use gtk::{gdk, gio, glib};
-    use std::cell::RefCell;
+    use std::cell::{Cell, RefCell};
 
+    use crate::audit_log::{self, AuditEntry};
+    use crate::cert_monitor;
     use crate::connection_config::ConnectionConfig;
+    use crate::connection_profiles::ConnectionProfile;
     use crate::data_gobject::DataGObject;
+    use crate::dbus_service;
     use crate::language_region_notify_popup::LanguageRegionNotifyPopup;
+    use crate::launch_args::Destination;
     use crate::locale_provider::{LanguageRegionData, LocaleProvider};
+    use crate::notifications;
+    use crate::preferences::Preferences;
     use crate::prelude::*;
     use crate::service_model::ServiceModel;
 
@@ -38,9 +48,27 @@ mod imp {
     #[properties(wrapper_type = super::ControlPanelGuiApplication)]
     pub struct ControlPanelGuiApplication {
         pub(super) service_model: ServiceModel,
+        pub(super) preferences: RefCell<Preferences>,
 
         #[property(get, set)]
         window: RefCell<Option<ControlPanelGuiWindow>>,
+
+        // Keep running without a window: closing the window only hides it
+        #[property(get, set)]
+        background: Cell<bool>,
+        background_hold: RefCell<Option<gio::ApplicationHoldGuard>>,
+        activated: Cell<bool>,
+        // Unit to select once the admin service reports it
+        pub(super) pending_select: RefCell<Option<String>>,
+        // What the model is connected with
+        pub(super) profile: RefCell<ConnectionProfile>,
+
+        // Warn about certificates expiring within this many days
+        #[property(get, set)]
+        cert_warning_days: Cell<u32>,
+        // Expiry of the certificates, for the About page
+        #[property(get, set)]
+        certificate_status: RefCell<String>,
     }
 
     #[glib::object_subclass]
@@ -55,19 +83,76 @@ mod imp {
         fn constructed(&self) {
             self.parent_constructed();
             let obj = self.obj();
+            self.preferences.replace(Preferences::load());
             self.setup_gactions();
+            self.service_model.connect_service_transition(glib::clone!(
+                #[weak(rename_to = app)]
+                self.obj(),
+                move |_, service, status, trust_level, operation| {
+                    notifications::service_transition(
+                        &app,
+                        service,
+                        status,
+                        trust_level,
+                        operation,
+                    );
+                }
+            ));
+            self.service_model
+                .connect_server_certificate_recorded(glib::clone!(
+                    #[weak(rename_to = app)]
+                    self.obj(),
+                    move |_, presented| app.record_server_certificate(Some(presented.clone()))
+                ));
+            self.service_model
+                .connect_server_certificate_changed(glib::clone!(
+                    #[weak(rename_to = app)]
+                    self.obj(),
+                    move |_, pinned, presented| {
+                        app.activate();
+                        if let Some(window) = app.window() {
+                            window.ask_trust_server_certificate(pinned, presented);
+                        }
+                    }
+                ));
+            self.service_model.connect_items_changed(glib::clone!(
+                #[weak(rename_to = app)]
+                self.obj(),
+                move |_, _, _, _| {
+                    if app.imp().pending_select.borrow().is_some() {
+                        // Let the window's models catch up first
+                        glib::idle_add_local_once(move || app.select_pending());
+                    }
+                }
+            ));
             obj.set_accels_for_action("app.quit", &["<primary>q"]);
             obj.set_accels_for_action("app.reconnect", &["<primary>r"]);
         }
     }
 
     impl ApplicationImpl for ControlPanelGuiApplication {
+        // Only the primary instance gets here, so only it serves D-Bus
+        fn startup(&self) {
+            self.parent_startup();
+            dbus_service::export(&self.obj());
+            cert_monitor::start(&self.obj());
+            if self.background.get() {
+                self.background_hold.replace(Some(self.obj().hold()));
+            }
+        }
+
         // We connect to the activate callback to create a window when the application
         // has been launched. Additionally, this callback notifies us when the user
         // tries to launch a "second instance" of the application. When they try
         // to do that, we'll just present any existing window.
         fn activate(&self) {
             let application = self.obj();
+            // In the background the own launch only starts the service, later launches
+            // show the window
+            if !self.activated.replace(true) && application.background() {
+                info!("Running in the background");
+                return;
+            }
             //load CSS styles
             Self::load_css();
             // Get the current window or create one if necessary
@@ -132,14 +217,19 @@ mod imp {
                 #[strong(rename_to = app)]
                 self.obj(),
                 async move {
-                    let locset = app.imp().service_model.set_locale(locale);
-                    if let Err(err) = locset.await {
+                    let action = format!("SetLocale {locale}");
+                    let locset = app.imp().service_model.set_locale(locale).await;
+                    if let Err(err) = &locset {
                         warn!("Locale setting failed: {err}");
                     }
-                    let tzset = app.imp().service_model.set_timezone(timezone);
-                    if let Err(err) = tzset.await {
+                    audit_log::record(&AuditEntry::new("", "", action, &locset));
+
+                    let action = format!("SetTimezone {timezone}");
+                    let tzset = app.imp().service_model.set_timezone(timezone).await;
+                    if let Err(err) = &tzset {
                         warn!("Timezone setting failed: {err}");
                     }
+                    audit_log::record(&AuditEntry::new("", "", action, &tzset));
 
                     popup.present();
                 }
@@ -160,30 +250,113 @@ mod imp {
             let show_config_action = Self::build_action("show-config", Self::show_config);
             let quit_action = Self::build_action("quit", Self::clean_n_quit);
             let about_action = Self::build_action("about", Self::show_about);
-            self.obj()
-                .add_action_entries([show_config_action, quit_action, about_action]);
+            let reconnect_action = Self::build_action("reconnect", Self::reconnect);
+            let skip_confirm = self.preferences.borrow().skip_confirm_app_vms;
+            let skip_confirm_action = gio::ActionEntry::builder("skip-confirm-app-vms")
+                .state(skip_confirm.to_variant())
+                .change_state(|app: &super::ControlPanelGuiApplication, action, value| {
+                    let Some(skip) = value.and_then(glib::Variant::get::<bool>) else {
+                        return;
+                    };
+                    action.set_state(&skip.to_variant());
+                    app.update_preferences(|prefs| prefs.skip_confirm_app_vms = skip);
+                })
+                .build();
+            self.obj().add_action_entries([
+                show_config_action,
+                quit_action,
+                about_action,
+                reconnect_action,
+                skip_confirm_action,
+            ]);
+            self.setup_notification_actions();
+        }
+
+        fn setup_notification_actions(&self) {
+            type App = super::ControlPanelGuiApplication;
+            // Clicking a notification shows its unit
+            let select_service_action = gio::ActionEntry::builder("select-service")
+                .parameter_type(Some(glib::VariantTy::STRING))
+                .activate(|app: &App, _, parameter| {
+                    let Some(name) = parameter.and_then(glib::Variant::str) else {
+                        return;
+                    };
+                    app.activate();
+                    if let Some(window) = app.window()
+                        && !window.select_service(name)
+                    {
+                        debug!("Cannot select {name}, it is gone or filtered out");
+                    }
+                })
+                .build();
+            let mute_vm_action = gio::ActionEntry::builder("mute-vm")
+                .parameter_type(Some(glib::VariantTy::STRING))
+                .activate(|app: &App, _, parameter| {
+                    let Some(vm_name) = parameter.and_then(glib::Variant::str) else {
+                        return;
+                    };
+                    info!("Muting notifications of {vm_name}");
+                    app.update_preferences(|prefs| {
+                        prefs.muted_vms.insert(vm_name.to_owned());
+                    });
+                })
+                .build();
+            // Notifications that are not about one unit open a page
+            let open_page_action = gio::ActionEntry::builder("open-page")
+                .parameter_type(Some(glib::VariantTy::STRING))
+                .activate(|app: &App, _, parameter| {
+                    if let Some(page) = parameter.and_then(glib::Variant::str) {
+                        app.open_destination(Destination {
+                            page: Some(page.to_owned()),
+                            select: None,
+                        });
+                    }
+                })
+                .build();
+            let unmute_all_action = Self::build_action("unmute-all-vms", |this| {
+                this.obj()
+                    .update_preferences(|prefs| prefs.muted_vms.clear());
+            });
+            let unexpected_only = self.preferences.borrow().notify_unexpected_only;
+            let unexpected_only_action = gio::ActionEntry::builder("notify-unexpected-only")
+                .state(unexpected_only.to_variant())
+                .activate(|app: &App, action, _| {
+                    let active = !action
+                        .state()
+                        .is_some_and(|state| state.get() == Some(true));
+                    action.set_state(&active.to_variant());
+                    app.update_preferences(|prefs| prefs.notify_unexpected_only = active);
+                })
+                .build();
+            self.obj().add_action_entries([
+                select_service_action,
+                open_page_action,
+                mute_vm_action,
+                unmute_all_action,
+                unexpected_only_action,
+            ]);
+        }
+
+        fn reconnect(&self) {
+            self.service_model.reconnect_now();
         }
 
         fn show_config(&self) {
-            let addr = self.service_model.address();
-            let port = self.service_model.port().try_into().unwrap_or(0u16);
-            let config = ConnectionConfig::new(&addr, port);
+            let config = ConnectionConfig::new(&self.profile.borrow());
             config.set_transient_for(self.obj().active_window().as_ref());
             config.set_modal(true);
 
             config.connect_local(
-                "new-config-applied",
+                "profile-applied",
                 false,
                 glib::clone!(
                     #[strong(rename_to = app)]
                     self.obj(),
                     move |values| {
                         //the value[0] is self
-                        let addr = values[1].get::<String>().unwrap();
-                        let port = values[2].get::<u32>().unwrap();
-                        debug!("New config applied: address {addr}, port {port}");
-                        app.imp().service_model.set_address(addr);
-                        app.imp().service_model.set_port(port);
+                        let profile = values[1].get::<ConnectionProfile>().unwrap();
+                        app.apply_profile(profile);
+                        cert_monitor::check(&app);
                         None
                     }
                 ),
@@ -224,9 +397,7 @@ impl ControlPanelGuiApplication {
     pub fn new(
         application_id: &str,
         flags: gio::ApplicationFlags,
-        addr: String,
-        port: u16,
-        tls_info: Option<(String, TlsConfig)>,
+        profile: ConnectionProfile,
     ) -> Self {
         let _ = DataGObject::static_type();
         let _ = Plot::static_type();
@@ -239,17 +410,116 @@ impl ControlPanelGuiApplication {
             .property("flags", flags)
             .build();
 
-        app.imp().service_model.set_address(addr);
-        app.imp().service_model.set_port(u32::from(port));
-        if let Some((addr, tls_info)) = tls_info {
-            app.imp().service_model.set_tls_info(addr, tls_info);
-        }
+        app.apply_profile(profile);
 
         app
     }
 
-    pub fn get_model(&self) -> ListModel {
-        self.imp().service_model.clone().upcast()
+    /// Connect with `profile` from now on.
+    pub fn apply_profile(&self, profile: ConnectionProfile) {
+        let model = &self.imp().service_model;
+        debug!(
+            "Connecting with profile '{name}': {address}:{port}",
+            name = profile.name,
+            address = profile.address,
+            port = profile.port
+        );
+        model.set_tls_info(profile.tls_info());
+        model.set_server_pin(profile.tofu, profile.pinned.clone());
+        model.set_address(profile.address.clone());
+        model.set_port(u32::from(profile.port));
+        self.imp().profile.replace(profile);
+    }
+
+    /// Record `pinned` as the admin service certificate of the current profile, in the
+    /// saved profile too if it is one.
+    pub fn record_server_certificate(&self, pinned: Option<PinnedCertificate>) {
+        let mut profile = self.imp().profile.borrow_mut();
+        profile.pinned.clone_from(&pinned);
+        if profile.name.is_empty() {
+            return;
+        }
+        let mut profiles = match ConnectionProfiles::load() {
+            Ok(profiles) => profiles,
+            Err(e) => {
+                warn!("Not saving the pin, it is kept until exit: {e:#}");
+                return;
+            }
+        };
+        if !profiles.set_pinned(&profile.name, pinned) {
+            debug!(
+                "Profile {} is not saved, the pin is kept until exit",
+                profile.name
+            );
+            return;
+        }
+        if let Err(e) = profiles.save() {
+            warn!("Failed to save connection profiles: {e:#}");
+        }
+    }
+
+    /// Trust `presented` from now on, in place of the certificate recorded before, and
+    /// connect.
+    pub fn trust_server_certificate(&self, presented: PinnedCertificate) {
+        info!(
+            "Trusting admin service certificate {}",
+            presented.fingerprint
+        );
+        self.record_server_certificate(Some(presented.clone()));
+        self.imp()
+            .service_model
+            .set_server_pin(true, Some(presented));
+    }
+
+    /// Present the window on `destination`. A unit that is not known yet is selected as
+    /// soon as the admin service reports it.
+    pub fn open_destination(&self, destination: Destination) {
+        self.activate();
+        let Some(window) = self.window() else {
+            return;
+        };
+        if let Some(page) = destination.page
+            && !window.open_page(&page)
+        {
+            warn!("No page named {page}");
+        }
+        if let Some(name) = destination.select {
+            self.imp().pending_select.replace(Some(name));
+            self.select_pending();
+        }
+    }
+
+    fn select_pending(&self) {
+        let Some(window) = self.window() else {
+            return;
+        };
+        let mut pending = self.imp().pending_select.borrow_mut();
+        if let Some(name) = pending.as_deref()
+            && window.select_service(name)
+        {
+            pending.take();
+        }
+    }
+
+    pub fn profile(&self) -> ConnectionProfile {
+        self.imp().profile.borrow().clone()
+    }
+
+    pub fn service_model(&self) -> ServiceModel {
+        self.imp().service_model.clone()
+    }
+
+    pub fn preferences(&self) -> Preferences {
+        self.imp().preferences.borrow().clone()
+    }
+
+    /// Apply `f` to the preferences and store the result.
+    pub fn update_preferences(&self, f: impl FnOnce(&mut Preferences)) {
+        let mut preferences = self.imp().preferences.borrow_mut();
+        f(&mut preferences);
+        if let Err(e) = preferences.save() {
+            warn!("Failed to save preferences: {e:#}");
+        }
     }
 
     pub fn get_stats(
@@ -271,27 +541,50 @@ impl ControlPanelGuiApplication {
             #[strong(rename_to = app)]
             self,
             async move {
-                match action {
-                    ControlAction::Start => app
-                        .imp()
-                        .service_model
-                        .start_service(object)
-                        .await
-                        .map(|_| ()),
-                    ControlAction::Restart => app
-                        .imp()
-                        .service_model
-                        .restart_service(&object)
-                        .await
-                        .map(|_| ()),
-                    ControlAction::Pause => app.imp().service_model.pause_service(&object).await,
-                    ControlAction::Resume => app.imp().service_model.resume_service(&object).await,
-                    ControlAction::Shutdown => app.imp().service_model.stop_service(&object).await,
+                let result = app.run_control_action(action, &object, Origin::Panel).await;
+                if let Err(e) = &result {
+                    warn!("{action:?} of {name} failed: {e:#}", name = object.name());
+                }
+                if let Some(window) = app.window() {
+                    window.show_control_result(action, &object, &result);
                 }
             }
         ));
     }
 
+    /// Run `action` on `object` and record it in the audit log as asked for from `origin`.
+    pub async fn run_control_action(
+        &self,
+        action: ControlAction,
+        object: &ServiceGObject,
+        origin: Origin,
+    ) -> Result<(), anyhow::Error> {
+        let model = &self.imp().service_model;
+        object.begin_operation(action.into(), model.operation_timeout());
+        let result = match action {
+            ControlAction::Start => model.start_service(object.clone()).await.map(|_| ()),
+            ControlAction::Restart => model.restart_service(object).await.map(|_| ()),
+            ControlAction::Pause => model.pause_service(object).await,
+            ControlAction::Resume => model.resume_service(object).await,
+            ControlAction::Shutdown => model.stop_service(object).await,
+        };
+        // On success the operation ends once the unit reports the status it leads to, which
+        // may have come in already
+        if result.is_err() || object.operation().is_done(object.status()) {
+            object.end_operation();
+        }
+        audit_log::record(
+            &AuditEntry::new(
+                object.name(),
+                object.vm_name(),
+                format!("{action:?}"),
+                &result,
+            )
+            .with_origin(origin),
+        );
+        result
+    }
+
     fn open_wireguard(&self, vm: &ServiceGObject) {
         debug!("wireguard vm {vm}", vm = vm.name()); // Output: business-vm
 
@@ -302,11 +595,17 @@ impl ControlPanelGuiApplication {
                 #[strong(rename_to = app)]
                 self,
                 async move {
-                    app.imp()
+                    let result = app
+                        .imp()
                         .service_model
-                        .start_app_in_vm("wireguard-gui".into(), vm_name, vec![])
-                        .await
-                        .ok();
+                        .start_app_in_vm("wireguard-gui".into(), vm_name.clone(), vec![])
+                        .await;
+                    audit_log::record(&AuditEntry::new(
+                        "wireguard-gui",
+                        vm_name,
+                        "OpenWireGuard",
+                        &result,
+                    ));
                 }
             ));
         }
@@ -331,11 +630,15 @@ impl ControlPanelGuiApplication {
                 glib::spawn_future_local(glib::clone!(
                     #[strong(rename_to = app)]
                     self,
-                    async move { app.imp().service_model.check_for_update().await }
+                    async move {
+                        let result = app.imp().service_model.check_for_update().await;
+                        audit_log::record(&AuditEntry::new("", "", "CheckForUpdate", &result));
+                    }
                 ));
             }
             SettingsAction::UpdateRequest => {
                 self.imp().service_model.update_request();
+                audit_log::record(&AuditEntry::new("", "", "UpdateRequest", &Ok(())));
             }
         }
     }
@@ -0,0 +1,130 @@
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+
+use gtk::glib;
+
+use crate::prelude::*;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// Where an action was asked for.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Origin {
+    /// The control panel window
+    #[default]
+    Panel,
+    /// A method call on the session bus
+    DBus,
+    /// The `ghaf-ctl` command
+    Cli,
+}
+
+impl Origin {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Panel => "panel",
+            Self::DBus => "D-Bus",
+            Self::Cli => "ghaf-ctl",
+        }
+    }
+}
+
+/// One line of the audit log.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Local time in RFC 3339 format
+    pub timestamp: String,
+    pub service: String,
+    pub vm_name: String,
+    pub action: String,
+    pub result: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub origin: Origin,
+}
+
+impl AuditEntry {
+    pub fn new<T>(
+        service: impl Into<String>,
+        vm_name: impl Into<String>,
+        action: impl Into<String>,
+        result: &Result<T, anyhow::Error>,
+    ) -> Self {
+        let (result, error) = match result {
+            Ok(_) => (Outcome::Success, None),
+            Err(e) => (Outcome::Failure, Some(format!("{e:#}"))),
+        };
+        Self {
+            timestamp: chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+            service: service.into(),
+            vm_name: vm_name.into(),
+            action: action.into(),
+            result,
+            error,
+            origin: Origin::default(),
+        }
+    }
+
+    /// The same entry, for an action asked for from `origin`.
+    pub fn with_origin(self, origin: Origin) -> Self {
+        Self { origin, ..self }
+    }
+}
+
+pub fn path() -> PathBuf {
+    glib::user_data_dir().join("ctrl-panel/audit.jsonl")
+}
+
+/// Append `entry` to the audit log. Failures are logged, never propagated: the action
+/// itself has already happened.
+pub fn record(entry: &AuditEntry) {
+    let write = || -> Result<(), anyhow::Error> {
+        let path = path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)?
+            .write_all(line.as_bytes())?;
+        Ok(())
+    };
+    if let Err(e) = write() {
+        error!("Failed to write audit log entry {entry:?}: {e:#}");
+    }
+}
+
+/// Read back the whole audit log, oldest entry first. Lines that don't parse are skipped.
+pub fn load() -> Vec<AuditEntry> {
+    let path = path();
+    let file = match std::fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return vec![],
+        Err(e) => {
+            warn!("Cannot read audit log {path}: {e}", path = path.display());
+            return vec![];
+        }
+    };
+    BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| {
+            serde_json::from_str(&line)
+                .inspect_err(|e| warn!("Skipping malformed audit log line: {e}"))
+                .ok()
+        })
+        .collect()
+}
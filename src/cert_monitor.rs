@@ -0,0 +1,109 @@
+//! Checks the TLS certificates the panel connects with, at startup and then daily, and
+//! warns ahead of their expiry: with a notification, and on the About page.
+
+use chrono::{DateTime, Utc};
+use gtk::prelude::*;
+use gtk::{gio, glib};
+
+use crate::application::ControlPanelGuiApplication;
+use crate::prelude::*;
+use crate::tls_certificates::{CertificateInfo, TIME_FORMAT};
+
+const CHECK_INTERVAL_SECS: u32 = 24 * 60 * 60;
+const NOTIFICATION_ID: &str = "cert-expiry";
+
+/// Check now, then daily for as long as the application runs.
+pub fn start(app: &ControlPanelGuiApplication) {
+    check(app);
+    glib::timeout_add_seconds_local(
+        CHECK_INTERVAL_SECS,
+        glib::clone!(
+            #[weak]
+            app,
+            #[upgrade_or]
+            glib::ControlFlow::Break,
+            move || {
+                check(&app);
+                glib::ControlFlow::Continue
+            }
+        ),
+    );
+}
+
+fn days_left(not_after: DateTime<Utc>, now: DateTime<Utc>) -> String {
+    match (not_after - now).num_days() {
+        0 => String::from("today"),
+        1 => String::from("tomorrow"),
+        days => format!("in {days} days"),
+    }
+}
+
+/// Check the certificates of the current connection profile, show the result on the
+/// About page and notify about those expiring within the warning period.
+pub fn check(app: &ControlPanelGuiApplication) {
+    let profile = app.profile();
+    if profile.no_tls {
+        app.set_certificate_status("Not used, TLS is off");
+        app.withdraw_notification(NOTIFICATION_ID);
+        return;
+    }
+
+    let now = Utc::now();
+    let warning_days = i64::from(app.cert_warning_days());
+    let mut lines = Vec::new();
+    let mut warnings = Vec::new();
+    let mut expired = false;
+    for (what, path) in [
+        ("CA certificate", &profile.ca_cert),
+        ("Host certificate", &profile.cert),
+    ] {
+        let chain = match CertificateInfo::load_chain(path) {
+            Ok(chain) if chain.is_empty() => {
+                lines.push(format!("{what}: none in {}", path.display()));
+                continue;
+            }
+            Ok(chain) => chain,
+            Err(e) => {
+                warn!("Certificate check: {e:#}");
+                lines.push(format!("{what}: {e:#}"));
+                continue;
+            }
+        };
+        for cert in chain {
+            let until = cert.not_after.format(TIME_FORMAT);
+            let line = if cert.not_after < now {
+                expired = true;
+                format!("{what} {}: expired on {until}", cert.subject)
+            } else if (cert.not_after - now).num_days() < warning_days {
+                format!(
+                    "{what} {}: expires {}, on {until}",
+                    cert.subject,
+                    days_left(cert.not_after, now)
+                )
+            } else {
+                lines.push(format!("{what} {}: valid until {until}", cert.subject));
+                continue;
+            };
+            warn!("{line}");
+            warnings.push(line.clone());
+            lines.push(line);
+        }
+    }
+    app.set_certificate_status(lines.join("\n"));
+
+    if warnings.is_empty() {
+        app.withdraw_notification(NOTIFICATION_ID);
+        return;
+    }
+    let (title, priority) = if expired {
+        ("Certificate expired", gio::NotificationPriority::Urgent)
+    } else {
+        ("Certificate expires soon", gio::NotificationPriority::High)
+    };
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(&warnings.join("\n")));
+    notification.set_priority(priority);
+    notification
+        .set_default_action_and_target_value("app.open-page", Some(&"settings/about".to_variant()));
+    app.send_notification(Some(NOTIFICATION_ID), &notification);
+}
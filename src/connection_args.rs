@@ -0,0 +1,169 @@
+use anyhow::Context;
+use clap::Args;
+use std::path::PathBuf;
+
+use crate::connection_profiles::{ConnectionProfile, ConnectionProfiles};
+use crate::prelude::*;
+
+pub const ADMIN_SERVICE_ADDR: &str = "192.168.101.10";
+pub const ADMIN_SERVICE_PORT: u16 = 9001;
+
+/// How to reach the admin service, shared by the binaries that talk to it.
+#[derive(Args, Debug, Clone)]
+pub struct ConnectionArgs {
+    /// Saved connection profile to use. Without it, and without --addr, the default
+    /// profile is used if there is one. The other options given apply on top of it
+    #[arg(long)]
+    profile: Option<String>,
+
+    /// Admin service host, `unix:///path/to.sock` or `vsock://<cid>:<port>`
+    #[arg(long)]
+    addr: Option<String>,
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// TLS name of the admin service [default: admin-vm]
+    #[arg(long, env = "NAME")]
+    name: Option<String>,
+
+    /// CA certificate of the admin service [default: /run/givc/ca-cert.pem]
+    #[arg(long, env = "CA_CERT")]
+    cacert: Option<PathBuf>,
+
+    /// Client certificate [default: /run/givc/cert.pem]
+    #[arg(long, env = "HOST_CERT")]
+    cert: Option<PathBuf>,
+
+    /// Client key [default: /run/givc/key.pem]
+    #[arg(long, env = "HOST_KEY")]
+    key: Option<PathBuf>,
+
+    #[arg(long, default_value_t)]
+    notls: bool,
+
+    /// Trust the admin service certificate seen on the first connection, and refuse to
+    /// connect if it changes
+    #[arg(long, default_value_t)]
+    tofu: bool,
+
+    /// Scenario file for the in-process fake admin service, TOML or JSON. The built-in
+    /// scenario is used if not given
+    #[cfg(feature = "mock")]
+    #[arg(long)]
+    mock_scenario: Option<PathBuf>,
+}
+
+impl ConnectionArgs {
+    /// The connection to use: the profile named by `--profile`, else the default profile
+    /// unless `--addr` is given, with the other options applied on top. Profiles are not
+    /// read when `--addr` is given alone.
+    pub fn profile(&self) -> Result<ConnectionProfile, anyhow::Error> {
+        let saved = if self.profile.is_some() || self.addr.is_none() {
+            let profiles = ConnectionProfiles::load()?;
+            match &self.profile {
+                Some(name) => Some(
+                    profiles
+                        .get(name)
+                        .cloned()
+                        .with_context(|| format!("No connection profile named {name}"))?,
+                ),
+                None => profiles.default_profile().cloned(),
+            }
+        } else {
+            None
+        };
+
+        let profile = match saved {
+            Some(saved) => {
+                let profile = self.apply(saved.clone());
+                if profile == saved {
+                    saved
+                } else {
+                    // Not what is saved under that name, so nothing is recorded in it
+                    debug!("Options change profile {}, not saving to it", saved.name);
+                    ConnectionProfile {
+                        name: String::new(),
+                        ..profile
+                    }
+                }
+            }
+            None => self.apply(ConnectionProfile::default()),
+        };
+        profile.check_tofu()?;
+        Ok(profile)
+    }
+
+    /// `profile` with the options given on the command line or in the environment.
+    fn apply(&self, profile: ConnectionProfile) -> ConnectionProfile {
+        ConnectionProfile {
+            address: self.addr.clone().unwrap_or(profile.address),
+            port: self.port.unwrap_or(profile.port),
+            tls_name: self.name.clone().unwrap_or(profile.tls_name),
+            ca_cert: self.cacert.clone().unwrap_or(profile.ca_cert),
+            cert: self.cert.clone().unwrap_or(profile.cert),
+            key: self.key.clone().unwrap_or(profile.key),
+            no_tls: self.notls || profile.no_tls,
+            tofu: self.tofu || profile.tofu,
+            ..profile
+        }
+    }
+
+    #[cfg(feature = "mock")]
+    pub fn mock_scenario(&self) -> Result<crate::scenario::Scenario, anyhow::Error> {
+        use crate::scenario::Scenario;
+
+        self.mock_scenario
+            .as_deref()
+            .map_or_else(|| Ok(Scenario::builtin()), Scenario::load)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::Parser;
+
+    use super::*;
+
+    #[derive(Parser)]
+    struct Cli {
+        #[command(flatten)]
+        connection: ConnectionArgs,
+    }
+
+    fn args(options: &[&str]) -> ConnectionArgs {
+        Cli::parse_from(std::iter::once("ctrl-panel").chain(options.iter().copied())).connection
+    }
+
+    #[test]
+    fn options_apply_on_top_of_profiles() {
+        let saved = ConnectionProfile {
+            name: String::from("lab"),
+            address: String::from("10.0.0.1"),
+            ca_cert: PathBuf::from("/etc/lab/ca-cert.pem"),
+            tofu: true,
+            ..ConnectionProfile::default()
+        };
+        assert_eq!(args(&[]).apply(saved.clone()), saved);
+        assert_eq!(
+            args(&["--port", "9002", "--cacert", "/tmp/ca-cert.pem"]).apply(saved.clone()),
+            ConnectionProfile {
+                port: 9002,
+                ca_cert: PathBuf::from("/tmp/ca-cert.pem"),
+                ..saved
+            }
+        );
+    }
+
+    #[test]
+    fn defaults_without_profile() {
+        let profile = args(&["--addr", "10.0.0.2", "--notls"]).profile().unwrap();
+        assert_eq!(
+            profile,
+            ConnectionProfile {
+                address: String::from("10.0.0.2"),
+                no_tls: true,
+                ..ConnectionProfile::default()
+            }
+        );
+    }
+}
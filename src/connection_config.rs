@@ -1,30 +1,79 @@
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{gio, glib};
+use std::path::PathBuf;
+
+use crate::connection_profiles::ConnectionProfile;
+use crate::endpoint;
 
 mod imp {
-    use glib::Binding;
     use glib::subclass::Signal;
     use gtk::prelude::*;
     use gtk::subclass::prelude::*;
-    use gtk::{Button, CompositeTemplate, Entry, glib};
+    use gtk::{
+        Button, CheckButton, CompositeTemplate, DropDown, Entry, Label, StringList, gio, glib,
+    };
     use std::cell::RefCell;
+    use std::path::Path;
     use std::sync::OnceLock;
 
+    use crate::connection_profiles::{ConnectionProfile, ConnectionProfiles, PinnedCertificate};
+    use crate::prelude::*;
+    use crate::tls_certificates::{self, CertificateInfo};
+
     #[derive(Default, CompositeTemplate)]
     #[template(resource = "/ae/tii/ghaf/controlpanelgui/ui/connection_config.ui")]
     pub struct ConnectionConfig {
+        #[template_child]
+        pub profile_dropdown: TemplateChild<DropDown>,
+        #[template_child]
+        pub profile_names: TemplateChild<StringList>,
+        #[template_child]
+        pub name_entry: TemplateChild<Entry>,
         #[template_child]
         pub address_entry: TemplateChild<Entry>,
         #[template_child]
         pub port_entry: TemplateChild<Entry>,
         #[template_child]
+        pub tls_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub tls_name_entry: TemplateChild<Entry>,
+        #[template_child]
+        pub ca_cert_entry: TemplateChild<Entry>,
+        #[template_child]
+        pub ca_cert_info: TemplateChild<Label>,
+        #[template_child]
+        pub cert_entry: TemplateChild<Entry>,
+        #[template_child]
+        pub cert_info: TemplateChild<Label>,
+        #[template_child]
+        pub key_entry: TemplateChild<Entry>,
+        #[template_child]
+        pub key_info: TemplateChild<Label>,
+        #[template_child]
+        pub no_tls_check: TemplateChild<CheckButton>,
+        #[template_child]
+        pub tofu_check: TemplateChild<CheckButton>,
+        #[template_child]
+        pub pinned_box: TemplateChild<gtk::Box>,
+        #[template_child]
+        pub pinned_label: TemplateChild<Label>,
+        #[template_child]
+        pub default_check: TemplateChild<CheckButton>,
+        #[template_child]
+        pub status_label: TemplateChild<Label>,
+        #[template_child]
+        pub delete_button: TemplateChild<Button>,
+        #[template_child]
         pub apply_button: TemplateChild<Button>,
         #[template_child]
         pub cancel_button: TemplateChild<Button>,
 
-        // Vector holding the bindings to properties of `Object`
-        pub bindings: RefCell<Vec<Binding>>,
+        pub profiles: RefCell<ConnectionProfiles>,
+        // Why the saved profiles could not be loaded, they are not saved over then
+        pub load_error: RefCell<Option<String>>,
+        // Admin service certificate recorded for the profile being edited
+        pub pinned: RefCell<Option<PinnedCertificate>>,
     }
 
     #[glib::object_subclass]
@@ -43,13 +92,235 @@ mod imp {
         }
     }
 
+    impl ConnectionConfig {
+        /// Refill the dropdown from the saved profiles and select `selected`. Entry 0 is the
+        /// unsaved connection being edited.
+        pub(super) fn refresh_profiles(&self, selected: &str) {
+            let profiles = self.profiles.borrow();
+            let names: Vec<&str> = profiles.profiles.iter().map(|p| p.name.as_str()).collect();
+            self.profile_names
+                .splice(0, self.profile_names.n_items(), &["(unsaved)"]);
+            self.profile_names.splice(1, 0, &names);
+            let position = names
+                .iter()
+                .position(|name| *name == selected)
+                .map_or(0, |i| u32::try_from(i + 1).unwrap_or(0));
+            self.profile_dropdown.set_selected(position);
+            self.delete_button.set_sensitive(position != 0);
+        }
+
+        fn selected_name(&self) -> Option<String> {
+            self.profile_dropdown
+                .selected_item()
+                .and_downcast::<gtk::StringObject>()
+                .filter(|_| self.profile_dropdown.selected() != 0)
+                .map(|item| item.string().into())
+        }
+
+        pub(super) fn set_pinned(&self, pinned: Option<PinnedCertificate>) {
+            if let Some(pinned) = &pinned {
+                self.pinned_label.set_label(&format!(
+                    "Recorded certificate {}\nSHA-256 {}",
+                    pinned.subject, pinned.fingerprint
+                ));
+            }
+            self.pinned_box.set_visible(pinned.is_some());
+            self.pinned.replace(pinned);
+        }
+
+        fn set_status(&self, message: &str) {
+            self.status_label.set_label(message);
+            self.status_label.set_visible(!message.is_empty());
+        }
+
+        /// Whether the saved profiles were loaded, and may be saved over.
+        pub(super) fn check_loaded(&self) -> bool {
+            let load_error = self.load_error.borrow();
+            if let Some(e) = &*load_error {
+                self.set_status(&format!("{e}\nFix or remove the file to save connections"));
+            }
+            load_error.is_none()
+        }
+
+        /// Show what each TLS file holds, or why it cannot be used.
+        fn update_tls_info(&self) {
+            for (entry, info) in [
+                (&self.ca_cert_entry, &self.ca_cert_info),
+                (&self.cert_entry, &self.cert_info),
+            ] {
+                let path = entry.text();
+                let text = if path.is_empty() {
+                    String::new()
+                } else {
+                    match CertificateInfo::load(Path::new(path.as_str())) {
+                        Ok(cert) => match cert.validity_problem() {
+                            Some(problem) => format!("{cert}\nNot usable: {problem}"),
+                            None => cert.to_string(),
+                        },
+                        Err(e) => format!("{e:#}"),
+                    }
+                };
+                info.set_label(&text);
+                info.set_visible(!text.is_empty());
+            }
+
+            let path = self.key_entry.text();
+            let text = if path.is_empty() {
+                String::new()
+            } else {
+                tls_certificates::check_key(Path::new(path.as_str()))
+                    .map_or_else(|e| format!("{e:#}"), |()| String::from("Private key"))
+            };
+            self.key_info.set_label(&text);
+            self.key_info.set_visible(!text.is_empty());
+        }
+
+        /// Let the user pick a PEM file for `entry`.
+        fn choose_file(&self, entry: &Entry, title: &str) {
+            let dialog = gtk::FileDialog::builder().title(title).modal(true).build();
+            let current = entry.text();
+            if !current.is_empty() {
+                dialog.set_initial_file(Some(&gio::File::for_path(current.as_str())));
+            }
+            dialog.open(
+                Some(&*self.obj()),
+                gio::Cancellable::NONE,
+                glib::clone!(
+                    #[weak]
+                    entry,
+                    move |result| match result {
+                        Ok(file) => {
+                            if let Some(path) = file.path() {
+                                entry.set_text(&path.to_string_lossy());
+                            }
+                        }
+                        Err(e) => debug!("No file chosen: {e}"),
+                    }
+                ),
+            );
+        }
+    }
+
     #[gtk::template_callbacks]
     impl ConnectionConfig {
+        #[template_callback]
+        fn on_profile_selected(&self) {
+            let Some(name) = self.selected_name() else {
+                self.delete_button.set_sensitive(false);
+                return;
+            };
+            let profiles = self.profiles.borrow();
+            if let Some(profile) = profiles.get(&name) {
+                self.obj().set_profile(profile);
+                self.default_check
+                    .set_active(profiles.default.as_deref() == Some(name.as_str()));
+            }
+            self.delete_button.set_sensitive(true);
+            self.set_status("");
+        }
+        #[template_callback]
+        fn on_tls_file_changed(&self) {
+            self.update_tls_info();
+        }
+        #[template_callback]
+        fn on_no_tls_toggled(&self) {
+            let no_tls = self.no_tls_check.is_active();
+            self.tls_box.set_sensitive(!no_tls);
+            self.tofu_check.set_sensitive(!no_tls);
+        }
+        #[template_callback]
+        fn on_browse_ca_cert_clicked(&self) {
+            self.choose_file(&self.ca_cert_entry, "Choose the CA certificate");
+        }
+        #[template_callback]
+        fn on_browse_cert_clicked(&self) {
+            self.choose_file(&self.cert_entry, "Choose the client certificate");
+        }
+        #[template_callback]
+        fn on_browse_key_clicked(&self) {
+            self.choose_file(&self.key_entry, "Choose the client key");
+        }
+        #[template_callback]
+        fn on_forget_pin_clicked(&self) {
+            self.set_pinned(None);
+            self.set_status("The certificate is recorded again on the next connection");
+        }
+        #[template_callback]
+        fn on_save_clicked(&self) {
+            if !self.check_loaded() {
+                return;
+            }
+            let profile = match self.obj().profile() {
+                Ok(profile) if profile.name.is_empty() => {
+                    self.set_status("Enter a name to save the connection");
+                    return;
+                }
+                Ok(profile) => profile,
+                Err(e) => {
+                    self.set_status(&e);
+                    return;
+                }
+            };
+            let name = profile.name.clone();
+            {
+                let mut profiles = self.profiles.borrow_mut();
+                profiles.upsert(profile);
+                if self.default_check.is_active() {
+                    profiles.default = Some(name.clone());
+                } else if profiles.default.as_deref() == Some(name.as_str()) {
+                    profiles.default = None;
+                }
+                if let Err(e) = profiles.save() {
+                    error!("Saving connection profiles failed: {e}");
+                    self.set_status(&format!("Saving failed: {e}"));
+                    return;
+                }
+            }
+            self.refresh_profiles(&name);
+            self.set_status(&format!("Saved {name}"));
+        }
+        #[template_callback]
+        fn on_delete_clicked(&self) {
+            let Some(name) = self.selected_name() else {
+                return;
+            };
+            if !self.check_loaded() {
+                return;
+            }
+            {
+                let mut profiles = self.profiles.borrow_mut();
+                profiles.remove(&name);
+                if let Err(e) = profiles.save() {
+                    error!("Saving connection profiles failed: {e}");
+                    self.set_status(&format!("Saving failed: {e}"));
+                    return;
+                }
+            }
+            self.refresh_profiles("");
+            self.set_status(&format!("Deleted {name}"));
+        }
         #[template_callback]
         fn on_apply_clicked(&self) {
-            let (addr, port) = self.obj().get_config();
-            self.obj()
-                .emit_by_name::<()>("new-config-applied", &[&addr, &port]);
+            // Refuse what cannot connect rather than fail in the background
+            let profile = self.obj().profile().and_then(|profile| {
+                tls_certificates::check_profile(&profile)
+                    .map(|()| profile)
+                    .map_err(|e| format!("{e:#}"))
+            });
+            // The certificate seen first could not be recorded in the saved profile
+            if matches!(&profile, Ok(profile) if profile.tofu && !profile.name.is_empty())
+                && !self.check_loaded()
+            {
+                return;
+            }
+            match profile {
+                Ok(profile) => {
+                    self.obj()
+                        .emit_by_name::<()>("profile-applied", &[&profile]);
+                    self.obj().close();
+                }
+                Err(e) => self.set_status(&e),
+            }
         }
         #[template_callback]
         fn on_cancel_clicked(&self) {
@@ -61,14 +332,23 @@ mod imp {
         fn constructed(&self) {
             // Call "constructed" on parent
             self.parent_constructed();
+            match ConnectionProfiles::load() {
+                Ok(profiles) => {
+                    self.profiles.replace(profiles);
+                }
+                Err(e) => {
+                    error!("{e:#}");
+                    self.load_error.replace(Some(format!("{e:#}")));
+                }
+            }
         }
 
         fn signals() -> &'static [Signal] {
             static SIGNALS: OnceLock<Vec<Signal>> = OnceLock::new();
             SIGNALS.get_or_init(|| {
                 vec![
-                    Signal::builder("new-config-applied")
-                        .param_types([String::static_type(), u32::static_type()])
+                    Signal::builder("profile-applied")
+                        .param_types([ConnectionProfile::static_type()])
                         .build(),
                 ]
             })
@@ -88,27 +368,73 @@ pub struct ConnectionConfig(ObjectSubclass<imp::ConnectionConfig>)
 
 impl Default for ConnectionConfig {
     fn default() -> Self {
-        Self::new("", 1)
+        Self::new(&ConnectionProfile::default())
     }
 }
 
 impl ConnectionConfig {
-    pub fn new(address: &str, port: u16) -> Self {
+    /// Edit `current`, the profile the panel is connected with.
+    pub fn new(current: &ConnectionProfile) -> Self {
         let config_widget: Self = glib::Object::builder().build();
-        config_widget.imp().address_entry.set_text(address);
-        config_widget
-            .imp()
-            .port_entry
-            .set_text(port.to_string().as_str());
+        let imp = config_widget.imp();
+        imp.refresh_profiles(&current.name);
+        // Show what is in use, even if the saved profile has changed since
+        config_widget.set_profile(current);
+        let is_default = imp.profiles.borrow().default.as_deref() == Some(current.name.as_str());
+        imp.default_check
+            .set_active(!current.name.is_empty() && is_default);
+        imp.check_loaded();
         config_widget
     }
 
-    pub fn get_config(&self) -> (String, u32) {
-        let text = self.imp().port_entry.text().to_string();
-        let port = text.parse::<u16>().unwrap_or(0);
-        (
-            String::from(self.imp().address_entry.buffer().text()),
-            port.into(),
-        )
+    fn set_profile(&self, profile: &ConnectionProfile) {
+        let imp = self.imp();
+        imp.name_entry.set_text(&profile.name);
+        imp.address_entry.set_text(&profile.address);
+        imp.port_entry.set_text(profile.port.to_string().as_str());
+        imp.tls_name_entry.set_text(&profile.tls_name);
+        imp.ca_cert_entry
+            .set_text(&profile.ca_cert.to_string_lossy());
+        imp.cert_entry.set_text(&profile.cert.to_string_lossy());
+        imp.key_entry.set_text(&profile.key.to_string_lossy());
+        imp.no_tls_check.set_active(profile.no_tls);
+        imp.tofu_check.set_active(profile.tofu);
+        imp.set_pinned(profile.pinned.clone());
+    }
+
+    /// The profile as entered, or what is wrong with it.
+    pub fn profile(&self) -> Result<ConnectionProfile, String> {
+        let imp = self.imp();
+        let address = imp.address_entry.text().trim().to_owned();
+        if address.is_empty() {
+            return Err(String::from("Enter an address"));
+        }
+        let port_text = imp.port_entry.text();
+        // Socket URIs need no port
+        let port = if endpoint::is_socket(&address) && port_text.trim().is_empty() {
+            0
+        } else {
+            port_text
+                .trim()
+                .parse::<u16>()
+                .ok()
+                .filter(|port| *port != 0)
+                .ok_or_else(|| format!("Invalid port: {port_text}"))?
+        };
+        endpoint::parse(&address, port).map_err(|e| format!("{e:#}"))?;
+        let profile = ConnectionProfile {
+            name: imp.name_entry.text().trim().to_owned(),
+            address,
+            port,
+            tls_name: imp.tls_name_entry.text().trim().to_owned(),
+            ca_cert: PathBuf::from(imp.ca_cert_entry.text().as_str()),
+            cert: PathBuf::from(imp.cert_entry.text().as_str()),
+            key: PathBuf::from(imp.key_entry.text().as_str()),
+            no_tls: imp.no_tls_check.is_active(),
+            tofu: imp.tofu_check.is_active(),
+            pinned: imp.pinned.borrow().clone(),
+        };
+        profile.check_tofu().map_err(|e| format!("{e:#}"))?;
+        Ok(profile)
     }
 }
@@ -0,0 +1,163 @@
+use anyhow::{Context, bail};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+use givc_client::endpoint::TlsConfig;
+use gtk::glib;
+
+use crate::connection_args::{ADMIN_SERVICE_ADDR, ADMIN_SERVICE_PORT};
+
+/// Why a profile that connects without TLS cannot trust on first use.
+pub const TOFU_NEEDS_TLS: &str = "Trust on first use needs TLS, a connection without it \
+    presents no certificate to pin. It needs no CA certificate, use it instead of connecting \
+    without TLS";
+
+/// How to reach one admin service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, glib::Boxed)]
+#[boxed_type(name = "CtrlConnectionProfile")]
+#[serde(default)]
+pub struct ConnectionProfile {
+    /// Empty for a connection that is not saved, e.g. one given on the command line
+    pub name: String,
+    pub address: String,
+    pub port: u16,
+    /// Server name checked against the certificate
+    pub tls_name: String,
+    pub ca_cert: PathBuf,
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    pub no_tls: bool,
+    /// Trust the admin service certificate seen on the first connection, and refuse to
+    /// connect if it changes
+    pub tofu: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pinned: Option<PinnedCertificate>,
+}
+
+/// Admin service certificate recorded on the first connection of a trust-on-first-use
+/// profile.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize, glib::Boxed)]
+#[boxed_type(name = "CtrlPinnedCertificate")]
+#[serde(default)]
+pub struct PinnedCertificate {
+    /// SHA-256 of the DER encoding, as colon separated hex
+    pub fingerprint: String,
+    pub subject: String,
+    pub issuer: String,
+    pub not_after: String,
+}
+
+impl Default for ConnectionProfile {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            address: String::from(ADMIN_SERVICE_ADDR),
+            port: ADMIN_SERVICE_PORT,
+            tls_name: String::from("admin-vm"),
+            ca_cert: PathBuf::from("/run/givc/ca-cert.pem"),
+            cert: PathBuf::from("/run/givc/cert.pem"),
+            key: PathBuf::from("/run/givc/key.pem"),
+            no_tls: false,
+            tofu: false,
+            pinned: None,
+        }
+    }
+}
+
+impl ConnectionProfile {
+    /// Refuse trust on first use without TLS.
+    pub fn check_tofu(&self) -> Result<(), anyhow::Error> {
+        if self.tofu && self.no_tls {
+            bail!(TOFU_NEEDS_TLS);
+        }
+        Ok(())
+    }
+
+    pub fn tls_info(&self) -> Option<(String, TlsConfig)> {
+        if self.no_tls {
+            return None;
+        }
+        Some((
+            self.tls_name.clone(),
+            TlsConfig {
+                ca_cert_file_path: self.ca_cert.clone(),
+                cert_file_path: self.cert.clone(),
+                key_file_path: self.key.clone(),
+                tls_name: Some(self.tls_name.clone()),
+            },
+        ))
+    }
+}
+
+/// Saved connection profiles, stored as TOML in the user config directory.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ConnectionProfiles {
+    /// Name of the profile to connect with at startup
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<String>,
+    #[serde(rename = "profile")]
+    pub profiles: Vec<ConnectionProfile>,
+}
+
+impl ConnectionProfiles {
+    pub fn path() -> PathBuf {
+        glib::user_config_dir().join("ctrl-panel/connections.toml")
+    }
+
+    /// The saved profiles, none if there is no file yet. A file that cannot be read or
+    /// parsed is an error, so that it is not saved over.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                toml::from_str(&content).with_context(|| format!("Malformed {}", path.display()))
+            }
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e).with_context(|| format!("Cannot read {}", path.display())),
+        }
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&ConnectionProfile> {
+        self.profiles.iter().find(|profile| profile.name == name)
+    }
+
+    pub fn default_profile(&self) -> Option<&ConnectionProfile> {
+        self.default.as_deref().and_then(|name| self.get(name))
+    }
+
+    /// Add `profile`, or replace the saved profile of the same name.
+    pub fn upsert(&mut self, profile: ConnectionProfile) {
+        if let Some(saved) = self.profiles.iter_mut().find(|p| p.name == profile.name) {
+            *saved = profile;
+        } else {
+            self.profiles.push(profile);
+        }
+    }
+
+    /// Record `pinned` as the certificate of the saved profile `name`. False if there is
+    /// no such profile.
+    pub fn set_pinned(&mut self, name: &str, pinned: Option<PinnedCertificate>) -> bool {
+        let Some(profile) = self.profiles.iter_mut().find(|p| p.name == name) else {
+            return false;
+        };
+        profile.pinned = pinned;
+        true
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.profiles.retain(|profile| profile.name != name);
+        if self.default.as_deref() == Some(name) {
+            self.default = None;
+        }
+    }
+}
@@ -0,0 +1,12 @@
+use gtk::glib;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "CtrlConnectionState")]
+#[repr(u8)]
+pub enum ConnectionState {
+    #[default]
+    Disconnected,
+    Connecting,
+    Connected,
+    Failed,
+}
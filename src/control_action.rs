@@ -1,3 +1,4 @@
+use givc_common::query::VMStatus;
 use gtk::glib;
 
 #[derive(Debug, Clone, Copy, glib::Enum)]
@@ -10,3 +11,87 @@ pub enum ControlAction {
     Resume,
     Shutdown,
 }
+
+/// Control action in flight on a unit, until its status change is reported back.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "CtrlServiceOperation")]
+#[repr(u8)]
+pub enum ServiceOperation {
+    #[default]
+    Idle,
+    Starting,
+    Stopping,
+    Pausing,
+    Resuming,
+}
+
+impl From<ControlAction> for ServiceOperation {
+    fn from(action: ControlAction) -> Self {
+        match action {
+            ControlAction::Start => Self::Starting,
+            ControlAction::Restart | ControlAction::Shutdown => Self::Stopping,
+            ControlAction::Pause => Self::Pausing,
+            ControlAction::Resume => Self::Resuming,
+        }
+    }
+}
+
+impl ServiceOperation {
+    /// Whether a unit reported in `status` is where the operation was taking it.
+    pub fn is_done(self, status: VMStatus) -> bool {
+        match self {
+            Self::Idle => true,
+            Self::Starting | Self::Resuming => matches!(status, VMStatus::Running),
+            Self::Stopping => matches!(status, VMStatus::PoweredOff),
+            Self::Pausing => matches!(status, VMStatus::Paused),
+        }
+    }
+}
+
+impl ControlAction {
+    /// Whether the action makes sense for a unit in the given state.
+    pub fn is_available(self, status: VMStatus) -> bool {
+        match self {
+            Self::Start => matches!(status, VMStatus::PoweredOff),
+            Self::Restart | Self::Pause => matches!(status, VMStatus::Running),
+            Self::Resume => matches!(status, VMStatus::Paused),
+            Self::Shutdown => matches!(status, VMStatus::Running | VMStatus::Paused),
+        }
+    }
+
+    /// Whether the action takes down the unit, and whatever runs inside it.
+    pub fn is_destructive(self) -> bool {
+        matches!(self, Self::Restart | Self::Shutdown)
+    }
+
+    pub fn verb(self) -> &'static str {
+        match self {
+            Self::Start => "start",
+            Self::Restart => "restart",
+            Self::Pause => "pause",
+            Self::Resume => "resume",
+            Self::Shutdown => "shut down",
+        }
+    }
+
+    /// Capitalized progressive form, for progress messages.
+    pub fn in_progress(self) -> &'static str {
+        match self {
+            Self::Start => "Starting",
+            Self::Restart => "Restarting",
+            Self::Pause => "Pausing",
+            Self::Resume => "Resuming",
+            Self::Shutdown => "Shutting down",
+        }
+    }
+
+    pub fn past_tense(self) -> &'static str {
+        match self {
+            Self::Start => "started",
+            Self::Restart => "restarted",
+            Self::Pause => "paused",
+            Self::Resume => "resumed",
+            Self::Shutdown => "shut down",
+        }
+    }
+}
@@ -0,0 +1,465 @@
+//! The `ae.tii.ghaf.ControlPanel` object on the session bus, so that the Ghaf taskbar and
+//! launcher can list and control units without speaking gRPC themselves.
+//!
+//! zbus runs the interface on its own executor thread, while the service model and the
+//! window live on the GTK main thread: method calls are forwarded there as `Request`s.
+
+use async_channel::Sender;
+use gtk::glib;
+use gtk::prelude::*;
+use serde::Serialize;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::Type;
+use zbus::{fdo, interface};
+
+use crate::application::ControlPanelGuiApplication;
+use crate::audit_log::Origin;
+use crate::control_action::{ControlAction, ServiceOperation};
+use crate::prelude::*;
+use crate::service_gobject::ServiceGObject;
+
+pub const BUS_NAME: &str = "ae.tii.ghaf.ControlPanel";
+pub const OBJECT_PATH: &str = "/ae/tii/ghaf/ControlPanel";
+
+/// A unit as returned by `ListServices`, signature `(ssssss)`.
+#[derive(Debug, Serialize, Type)]
+pub struct ServiceInfo {
+    name: String,
+    title: String,
+    vm_name: String,
+    /// `VM`, `App` or `Service`
+    kind: String,
+    status: String,
+    trust_level: String,
+}
+
+impl From<&ServiceGObject> for ServiceInfo {
+    fn from(obj: &ServiceGObject) -> Self {
+        let kind = if obj.is_vm() {
+            "VM"
+        } else if obj.is_app() {
+            "App"
+        } else {
+            "Service"
+        };
+        Self {
+            name: obj.name(),
+            title: obj.title(),
+            vm_name: obj.vm_name(),
+            kind: kind.to_owned(),
+            status: format!("{:?}", obj.status()),
+            trust_level: format!("{:?}", obj.trust_level()),
+        }
+    }
+}
+
+enum Request {
+    ListServices(Sender<Vec<ServiceInfo>>),
+    Control(ControlAction, String, Sender<fdo::Result<()>>),
+    OpenPage(String, Sender<fdo::Result<()>>),
+    SelectService(String, Sender<fdo::Result<()>>),
+}
+
+struct ControlPanel {
+    requests: Sender<Request>,
+}
+
+impl ControlPanel {
+    /// Send a request to the main thread and wait for its reply.
+    async fn call<T>(&self, request: impl FnOnce(Sender<T>) -> Request) -> fdo::Result<T> {
+        let gone = || fdo::Error::Failed(String::from("Control panel is shutting down"));
+        let (tx, rx) = async_channel::bounded(1);
+        self.requests.send(request(tx)).await.map_err(|_| gone())?;
+        rx.recv().await.map_err(|_| gone())
+    }
+}
+
+#[interface(name = "ae.tii.ghaf.ControlPanel")]
+impl ControlPanel {
+    async fn list_services(&self) -> fdo::Result<Vec<ServiceInfo>> {
+        self.call(Request::ListServices).await
+    }
+
+    async fn start(&self, name: String) -> fdo::Result<()> {
+        self.call(|tx| Request::Control(ControlAction::Start, name, tx))
+            .await?
+    }
+
+    async fn stop(&self, name: String) -> fdo::Result<()> {
+        self.call(|tx| Request::Control(ControlAction::Shutdown, name, tx))
+            .await?
+    }
+
+    async fn pause(&self, name: String) -> fdo::Result<()> {
+        self.call(|tx| Request::Control(ControlAction::Pause, name, tx))
+            .await?
+    }
+
+    async fn resume(&self, name: String) -> fdo::Result<()> {
+        self.call(|tx| Request::Control(ControlAction::Resume, name, tx))
+            .await?
+    }
+
+    /// Present the window on `page`: `services`, `settings`, `locale`, `history` or `about`.
+    async fn open_page(&self, page: String) -> fdo::Result<()> {
+        self.call(|tx| Request::OpenPage(page, tx)).await?
+    }
+
+    /// Present the window with the unit `name` selected.
+    async fn select_service(&self, name: String) -> fdo::Result<()> {
+        self.call(|tx| Request::SelectService(name, tx)).await?
+    }
+
+    #[zbus(signal)]
+    async fn service_status_changed(
+        emitter: &SignalEmitter<'_>,
+        name: &str,
+        vm_name: &str,
+        status: &str,
+    ) -> zbus::Result<()>;
+
+    /// `Disconnected`, `Connecting`, `Connected` or `Failed`
+    #[zbus(signal)]
+    async fn connection_state_changed(emitter: &SignalEmitter<'_>, state: &str)
+    -> zbus::Result<()>;
+}
+
+fn unknown_service(name: &str) -> fdo::Error {
+    fdo::Error::InvalidArgs(format!("No unit named {name}"))
+}
+
+fn check_controllable(action: ControlAction, obj: &ServiceGObject) -> fdo::Result<()> {
+    let name = obj.name();
+    if !obj.is_controllable() {
+        return Err(fdo::Error::AccessDenied(format!(
+            "{name} cannot be controlled"
+        )));
+    }
+    if !action.is_available(obj.status()) {
+        return Err(fdo::Error::Failed(format!(
+            "Cannot {action:?} {name} while {:?}",
+            obj.status()
+        )));
+    }
+    if obj.operation() != ServiceOperation::Idle {
+        return Err(fdo::Error::Failed(format!(
+            "{name} is busy: {:?}",
+            obj.operation()
+        )));
+    }
+    Ok(())
+}
+
+async fn handle(app: &ControlPanelGuiApplication, request: Request) {
+    // A caller that gave up waiting has dropped its receiver, nothing to do about it
+    match request {
+        Request::ListServices(reply) => {
+            let services = app.service_model().services();
+            let _ = reply
+                .send(services.iter().map(ServiceInfo::from).collect())
+                .await;
+        }
+        Request::Control(action, name, reply) => {
+            let Some(obj) = app.service_model().service(&name) else {
+                let _ = reply.send(Err(unknown_service(&name))).await;
+                return;
+            };
+            // The same units and states the window offers the action for
+            if let Err(e) = check_controllable(action, &obj) {
+                let _ = reply.send(Err(e)).await;
+                return;
+            }
+            // Not confirmed in the window, the caller is expected to have asked the user
+            let result = app.run_control_action(action, &obj, Origin::DBus).await;
+            if let Some(window) = app.window() {
+                window.show_control_result(action, &obj, &result);
+            }
+            let result = result.map_err(|e| fdo::Error::Failed(format!("{e:#}")));
+            let _ = reply.send(result).await;
+        }
+        Request::OpenPage(page, reply) => {
+            app.activate();
+            let result = match app.window() {
+                Some(window) if window.open_page(&page) => Ok(()),
+                _ => Err(fdo::Error::InvalidArgs(format!("No page named {page}"))),
+            };
+            let _ = reply.send(result).await;
+        }
+        Request::SelectService(name, reply) => {
+            app.activate();
+            let result = match app.window() {
+                Some(window) if window.select_service(&name) => Ok(()),
+                _ => Err(unknown_service(&name)),
+            };
+            let _ = reply.send(result).await;
+        }
+    }
+}
+
+async fn emit_service_status_changed(connection: zbus::Connection, obj: ServiceGObject) {
+    let result = async {
+        let emitter = SignalEmitter::new(&connection, OBJECT_PATH)?;
+        let status = format!("{:?}", obj.status());
+        ControlPanel::service_status_changed(&emitter, &obj.name(), &obj.vm_name(), &status).await
+    };
+    if let Err(e) = result.await {
+        warn!("Failed to emit ServiceStatusChanged: {e}");
+    }
+}
+
+async fn emit_connection_state_changed(connection: zbus::Connection, state: String) {
+    let result = async {
+        let emitter = SignalEmitter::new(&connection, OBJECT_PATH)?;
+        ControlPanel::connection_state_changed(&emitter, &state).await
+    };
+    if let Err(e) = result.await {
+        warn!("Failed to emit ConnectionStateChanged: {e}");
+    }
+}
+
+fn connect_signals(app: &ControlPanelGuiApplication, connection: &zbus::Connection) {
+    let model = app.service_model();
+    model.connect_service_changed(glib::clone!(
+        #[strong]
+        connection,
+        move |_, obj| {
+            glib::spawn_future_local(emit_service_status_changed(connection.clone(), obj.clone()));
+        }
+    ));
+    model.connect_connection_state_notify(glib::clone!(
+        #[strong]
+        connection,
+        move |model| {
+            let state = format!("{:?}", model.connection_state());
+            glib::spawn_future_local(emit_connection_state_changed(connection.clone(), state));
+        }
+    ));
+}
+
+/// Own `BUS_NAME` on the session bus and serve the control panel object from it. Without
+/// a session bus the panel runs on, just without the interface.
+pub fn export(app: &ControlPanelGuiApplication) {
+    let (tx, rx) = async_channel::unbounded();
+    glib::spawn_future_local(glib::clone!(
+        #[strong]
+        app,
+        async move {
+            let connection = async {
+                zbus::connection::Builder::session()?
+                    .name(BUS_NAME)?
+                    .serve_at(OBJECT_PATH, ControlPanel { requests: tx })?
+                    .build()
+                    .await
+            };
+            let connection = match connection.await {
+                Ok(connection) => connection,
+                Err(e) => {
+                    warn!("Cannot export {BUS_NAME} on the session bus: {e}");
+                    return;
+                }
+            };
+            info!("Serving {BUS_NAME} at {OBJECT_PATH}");
+            connect_signals(&app, &connection);
+
+            while let Ok(request) = rx.recv().await {
+                glib::spawn_future_local(glib::clone!(
+                    #[strong]
+                    app,
+                    async move { handle(&app, request).await }
+                ));
+            }
+        }
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader};
+    use std::process::{Child, Command, Stdio};
+
+    use super::*;
+
+    const VM: &str = "microvm@chrome-vm.service";
+
+    /// A dbus-daemon of its own, so that tests neither need nor touch the session bus.
+    struct PrivateBus {
+        daemon: Child,
+        address: String,
+    }
+
+    impl PrivateBus {
+        /// `None` where there is no dbus-daemon to run.
+        fn start() -> Option<Self> {
+            let mut daemon = match Command::new("dbus-daemon")
+                .args(["--session", "--nofork", "--print-address"])
+                .stdout(Stdio::piped())
+                .spawn()
+            {
+                Ok(daemon) => daemon,
+                Err(e) => {
+                    eprintln!("Skipped, cannot run dbus-daemon: {e}");
+                    return None;
+                }
+            };
+            let stdout = daemon.stdout.take().expect("stdout is piped");
+            let mut address = String::new();
+            BufReader::new(stdout)
+                .read_line(&mut address)
+                .expect("dbus-daemon prints its address");
+            Some(Self {
+                daemon,
+                address: address.trim().to_owned(),
+            })
+        }
+    }
+
+    impl Drop for PrivateBus {
+        fn drop(&mut self) {
+            let _ = self.daemon.kill();
+            let _ = self.daemon.wait();
+        }
+    }
+
+    /// Answer requests the way the main thread does, with `VM` the only unit. Control
+    /// requests are passed on to `controlled`.
+    fn answer(
+        requests: async_channel::Receiver<Request>,
+        controlled: Sender<(ControlAction, String)>,
+    ) {
+        std::thread::spawn(move || {
+            futures::executor::block_on(async move {
+                while let Ok(request) = requests.recv().await {
+                    match request {
+                        Request::ListServices(reply) => {
+                            let service = ServiceInfo {
+                                name: VM.to_owned(),
+                                title: String::from("chrome-vm"),
+                                vm_name: String::from("chrome-vm"),
+                                kind: String::from("VM"),
+                                status: String::from("Running"),
+                                trust_level: String::from("Secure"),
+                            };
+                            let _ = reply.send(vec![service]).await;
+                        }
+                        Request::Control(action, name, reply) => {
+                            let result = if name == VM {
+                                Ok(())
+                            } else {
+                                Err(unknown_service(&name))
+                            };
+                            let _ = controlled.send((action, name)).await;
+                            let _ = reply.send(result).await;
+                        }
+                        Request::OpenPage(_, reply) | Request::SelectService(_, reply) => {
+                            let _ = reply.send(Ok(())).await;
+                        }
+                    }
+                }
+            });
+        });
+    }
+
+    #[test]
+    fn method_calls_are_forwarded() {
+        let Some(bus) = PrivateBus::start() else {
+            return;
+        };
+        let (requests_tx, requests_rx) = async_channel::unbounded();
+        let (controlled_tx, controlled_rx) = async_channel::unbounded();
+        answer(requests_rx, controlled_tx);
+
+        futures::executor::block_on(async {
+            let _server = zbus::connection::Builder::address(bus.address.as_str())
+                .unwrap()
+                .name(BUS_NAME)
+                .unwrap()
+                .serve_at(
+                    OBJECT_PATH,
+                    ControlPanel {
+                        requests: requests_tx,
+                    },
+                )
+                .unwrap()
+                .build()
+                .await
+                .unwrap();
+            let client = zbus::connection::Builder::address(bus.address.as_str())
+                .unwrap()
+                .build()
+                .await
+                .unwrap();
+            let proxy = zbus::Proxy::new(&client, BUS_NAME, OBJECT_PATH, BUS_NAME)
+                .await
+                .unwrap();
+
+            let services: Vec<(String, String, String, String, String, String)> =
+                proxy.call("ListServices", &()).await.unwrap();
+            assert_eq!(services.len(), 1);
+            assert_eq!(services[0].0, VM);
+            assert_eq!(services[0].3, "VM");
+
+            let cases = [
+                ("Start", ControlAction::Start),
+                ("Stop", ControlAction::Shutdown),
+                ("Pause", ControlAction::Pause),
+                ("Resume", ControlAction::Resume),
+            ];
+            for (method, action) in cases {
+                proxy.call::<_, _, ()>(method, &(VM,)).await.unwrap();
+                let (forwarded, name) = controlled_rx.recv().await.unwrap();
+                assert_eq!(format!("{forwarded:?}"), format!("{action:?}"), "{method}");
+                assert_eq!(name, VM);
+            }
+
+            let error = proxy
+                .call::<_, _, ()>("Stop", &("unknown.service",))
+                .await
+                .unwrap_err();
+            assert!(
+                matches!(fdo::Error::from(error), fdo::Error::InvalidArgs(_)),
+                "Stop of an unknown unit"
+            );
+
+            proxy
+                .call::<_, _, ()>("OpenPage", &("about",))
+                .await
+                .unwrap();
+        });
+    }
+
+    #[test]
+    fn control_is_refused_where_the_window_refuses_it() {
+        use givc_common::query::{TrustLevel, VMStatus};
+        use givc_common::types::{ServiceType, VmType};
+
+        let unit = |status, vm_type| {
+            ServiceGObject::new(
+                VM,
+                "",
+                status,
+                TrustLevel::Secure,
+                ServiceType::VM,
+                Some("chrome-vm"),
+                vm_type,
+            )
+        };
+        let running = unit(VMStatus::Running, VmType::AppVM);
+        assert!(check_controllable(ControlAction::Pause, &running).is_ok());
+        assert!(matches!(
+            check_controllable(
+                ControlAction::Pause,
+                &unit(VMStatus::Running, VmType::SysVM)
+            ),
+            Err(fdo::Error::AccessDenied(_))
+        ));
+        assert!(matches!(
+            check_controllable(ControlAction::Resume, &running),
+            Err(fdo::Error::Failed(_))
+        ));
+        running.set_operation(ServiceOperation::Pausing);
+        assert!(matches!(
+            check_controllable(ControlAction::Pause, &running),
+            Err(fdo::Error::Failed(_))
+        ));
+    }
+}
@@ -0,0 +1,141 @@
+//! Where the admin service listens: a host and port, or a `unix://` or `vsock://` URI for
+//! configurations that expose it only over a local socket.
+
+use anyhow::{Context, bail};
+use givc_common::address::EndpointAddress;
+use tokio_vsock::VsockAddr;
+
+pub const UNIX_SCHEME: &str = "unix://";
+pub const VSOCK_SCHEME: &str = "vsock://";
+
+/// Whether `address` is a socket URI, for which the separate port does not apply.
+pub fn is_socket(address: &str) -> bool {
+    address.starts_with(UNIX_SCHEME) || address.starts_with(VSOCK_SCHEME)
+}
+
+/// The endpoint for `address`: `unix:///path/to.sock`, `vsock://<cid>:<port>` or a host.
+/// `port` is used for a host, and for a `vsock://` URI without a port.
+pub fn parse(address: &str, port: u16) -> Result<EndpointAddress, anyhow::Error> {
+    if let Some(path) = address.strip_prefix(UNIX_SCHEME) {
+        if !path.starts_with('/') {
+            bail!("Expected an absolute socket path in {address}");
+        }
+        return Ok(EndpointAddress::Unix(path.to_owned()));
+    }
+
+    if let Some(rest) = address.strip_prefix(VSOCK_SCHEME) {
+        let (cid, vsock_port) = match rest.split_once(':') {
+            Some((cid, vsock_port)) => (
+                cid,
+                vsock_port
+                    .parse::<u32>()
+                    .with_context(|| format!("Invalid vsock port in {address}"))?,
+            ),
+            None => (rest, u32::from(port)),
+        };
+        let cid = cid
+            .parse::<u32>()
+            .with_context(|| format!("Invalid vsock CID in {address}"))?;
+        if vsock_port == 0 {
+            bail!("No port given for {address}");
+        }
+        return Ok(EndpointAddress::Vsock(VsockAddr::new(cid, vsock_port)));
+    }
+
+    if port == 0 {
+        bail!("No port given for {address}");
+    }
+    Ok(EndpointAddress::Tcp {
+        addr: address.to_owned(),
+        port,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    enum Expected {
+        Tcp(&'static str, u16),
+        Unix(&'static str),
+        Vsock(u32, u32),
+    }
+
+    fn is(address: &EndpointAddress, expected: &Expected) -> bool {
+        match (address, expected) {
+            (EndpointAddress::Tcp { addr, port }, Expected::Tcp(host, tcp_port)) => {
+                addr == host && port == tcp_port
+            }
+            (EndpointAddress::Unix(path), Expected::Unix(socket)) => path == socket,
+            (EndpointAddress::Vsock(vsock), Expected::Vsock(cid, port)) => {
+                vsock.cid() == *cid && vsock.port() == *port
+            }
+            _ => false,
+        }
+    }
+
+    #[test]
+    fn parse_addresses() {
+        use Expected::{Tcp, Unix, Vsock};
+
+        let cases = [
+            ("admin-vm", 9001, Some(Tcp("admin-vm", 9001))),
+            ("192.168.100.1", 9001, Some(Tcp("192.168.100.1", 9001))),
+            ("192.168.100.1", 0, None),
+            (
+                "unix:///run/givc/admin.sock",
+                0,
+                Some(Unix("/run/givc/admin.sock")),
+            ),
+            // The port does not apply to a socket path
+            (
+                "unix:///run/givc/admin.sock",
+                9001,
+                Some(Unix("/run/givc/admin.sock")),
+            ),
+            ("unix://relative.sock", 0, None),
+            ("unix://run/admin.sock", 0, None),
+            ("unix://", 0, None),
+            ("vsock://3:9001", 0, Some(Vsock(3, 9001))),
+            ("vsock://3:9001", 9000, Some(Vsock(3, 9001))),
+            ("vsock://3", 9000, Some(Vsock(3, 9000))),
+            ("vsock://4294967295:1", 0, Some(Vsock(u32::MAX, 1))),
+            ("vsock://3", 0, None),
+            ("vsock://3:0", 9000, None),
+            ("vsock://3:", 9000, None),
+            ("vsock://:9001", 0, None),
+            ("vsock://host:9001", 0, None),
+            ("vsock://3:port", 0, None),
+            ("vsock://-1:9001", 0, None),
+            ("vsock://", 9000, None),
+        ];
+        for (address, port, expected) in cases {
+            match (parse(address, port), expected) {
+                (Ok(parsed), Some(expected)) => {
+                    assert!(is(&parsed, &expected), "{address} {port}: {expected:?}");
+                }
+                (Err(_), None) => {}
+                (Ok(_), None) => panic!("{address} {port}: accepted"),
+                (Err(e), Some(_)) => panic!("{address} {port}: {e:#}"),
+            }
+        }
+    }
+
+    #[test]
+    fn sockets() {
+        let cases = [
+            ("unix:///run/admin.sock", true),
+            ("unix://relative.sock", true),
+            ("vsock://3:9001", true),
+            ("vsock://3", true),
+            ("admin-vm", false),
+            ("unix:/run/admin.sock", false),
+            ("vsock:3", false),
+            ("", false),
+        ];
+        for (address, expected) in cases {
+            assert_eq!(is_socket(address), expected, "{address}");
+        }
+    }
+}
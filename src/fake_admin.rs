@@ -0,0 +1,260 @@
+//! Stand-in for the givc admin service, for running the control panel without Ghaf.
+//!
+//! Serves the admin gRPC API on a local TCP port without TLS. The units, their events and
+//! the VM stats come from a scenario file; start, stop, pause and resume requests update
+//! the units and are reported back over `Watch` like the real service would.
+
+mod fake_service;
+mod scenario;
+
+pub mod prelude {
+    pub use log::{debug, error, info, warn};
+}
+
+use clap::Parser;
+use std::net::SocketAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use futures::{Stream, StreamExt};
+use givc_common::pb::admin::admin_service_server::{AdminService, AdminServiceServer};
+use givc_common::pb::admin::{
+    ApplicationRequest, ApplicationResponse, Empty, QueryListItem, QueryListResponse,
+    RegistryRequest, RegistryResponse, StartResponse, StartServiceRequest, StartVmRequest,
+    StatsRequest, SysinfoResponse, WatchItem, watch_item,
+};
+use givc_common::pb::locale::{LocaleMessage, TimezoneMessage};
+use givc_common::pb::stats::StatsResponse;
+use tokio::net::UnixListener;
+use tokio_stream::wrappers::UnixListenerStream;
+use tonic::{Request, Response, Status};
+
+use crate::fake_service::{FakeError, FakeService, GHAF_VERSION};
+use crate::prelude::*;
+use crate::scenario::{Scenario, Unit};
+
+#[derive(Parser, Debug)]
+#[command(name = "fake-admin")]
+#[command(about = "Fake givc admin service for Ghaf Control Panel development", long_about = None)]
+struct Args {
+    /// Address to listen on, connect with `ctrl-panel --addr <ip> --port <port> --notls`
+    #[arg(long, default_value = "127.0.0.1:9001")]
+    listen: SocketAddr,
+
+    /// Listen on a unix socket instead, connect with
+    /// `ctrl-panel --addr unix://<path> --notls`
+    #[arg(long, conflicts_with = "listen")]
+    unix: Option<PathBuf>,
+
+    /// Scenario file with the units, events and stats to serve, TOML or JSON
+    #[arg(long)]
+    scenario: Option<PathBuf>,
+
+    /// Log severity
+    #[arg(long, default_value_t = log::Level::Info)]
+    log_level: log::Level,
+}
+
+impl From<FakeError> for Status {
+    fn from(e: FakeError) -> Self {
+        match e {
+            FakeError::UnitNotFound(_) | FakeError::VmNotFound(_) => {
+                Status::not_found(e.to_string())
+            }
+            FakeError::VmNotRunning(_) => Status::failed_precondition(e.to_string()),
+        }
+    }
+}
+
+fn app_response(status: &str) -> Response<ApplicationResponse> {
+    Response::new(ApplicationResponse {
+        cmd_status: String::from("Command successful."),
+        app_status: status.to_owned(),
+    })
+}
+
+type WatchStream = Pin<Box<dyn Stream<Item = Result<WatchItem, Status>> + Send>>;
+
+#[tonic::async_trait]
+impl AdminService for FakeService {
+    type WatchStream = WatchStream;
+
+    async fn register_service(
+        &self,
+        request: Request<RegistryRequest>,
+    ) -> Result<Response<RegistryResponse>, Status> {
+        debug!("RegisterService {:?}", request.into_inner());
+        Ok(Response::new(RegistryResponse::default()))
+    }
+
+    async fn start_application(
+        &self,
+        request: Request<ApplicationRequest>,
+    ) -> Result<Response<StartResponse>, Status> {
+        let request = request.into_inner();
+        let vm_name = request
+            .vm_name
+            .ok_or_else(|| Status::invalid_argument("VM name is required"))?;
+        self.start_app(&request.app_name, &vm_name, &request.args)?;
+        Ok(Response::new(StartResponse::default()))
+    }
+
+    async fn start_vm(
+        &self,
+        request: Request<StartVmRequest>,
+    ) -> Result<Response<StartResponse>, Status> {
+        FakeService::start_vm(self, &request.into_inner().vm_name)?;
+        Ok(Response::new(StartResponse::default()))
+    }
+
+    async fn start_service(
+        &self,
+        request: Request<StartServiceRequest>,
+    ) -> Result<Response<StartResponse>, Status> {
+        FakeService::start_service(self, &request.into_inner().service_name)?;
+        Ok(Response::new(StartResponse::default()))
+    }
+
+    async fn pause_application(
+        &self,
+        request: Request<ApplicationRequest>,
+    ) -> Result<Response<ApplicationResponse>, Status> {
+        self.pause(&request.into_inner().app_name)?;
+        Ok(app_response("Paused"))
+    }
+
+    async fn resume_application(
+        &self,
+        request: Request<ApplicationRequest>,
+    ) -> Result<Response<ApplicationResponse>, Status> {
+        self.resume(&request.into_inner().app_name)?;
+        Ok(app_response("Running"))
+    }
+
+    async fn stop_application(
+        &self,
+        request: Request<ApplicationRequest>,
+    ) -> Result<Response<ApplicationResponse>, Status> {
+        self.stop(&request.into_inner().app_name)?;
+        Ok(app_response("PoweredOff"))
+    }
+
+    async fn set_locale(&self, request: Request<LocaleMessage>) -> Result<Response<Empty>, Status> {
+        info!("SetLocale {:?}", request.into_inner());
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn set_timezone(
+        &self,
+        request: Request<TimezoneMessage>,
+    ) -> Result<Response<Empty>, Status> {
+        info!("SetTimezone {:?}", request.into_inner());
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn poweroff(&self, _request: Request<Empty>) -> Result<Response<Empty>, Status> {
+        info!("Poweroff");
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn reboot(&self, _request: Request<Empty>) -> Result<Response<Empty>, Status> {
+        info!("Reboot");
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn suspend(&self, _request: Request<Empty>) -> Result<Response<Empty>, Status> {
+        info!("Suspend");
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn wakeup(&self, _request: Request<Empty>) -> Result<Response<Empty>, Status> {
+        info!("Wakeup");
+        Ok(Response::new(Empty {}))
+    }
+
+    async fn query_list(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<QueryListResponse>, Status> {
+        Ok(Response::new(QueryListResponse {
+            list: self.units().iter().map(Unit::to_item).collect(),
+        }))
+    }
+
+    async fn watch(&self, _request: Request<Empty>) -> Result<Response<Self::WatchStream>, Status> {
+        let (units, changes) = self.subscribe();
+        let list: Vec<QueryListItem> = units.iter().map(Unit::to_item).collect();
+        info!("Watcher connected, {} units", list.len());
+
+        let initial = WatchItem {
+            status: Some(watch_item::Status::Initial(QueryListResponse { list })),
+        };
+        let changes = changes.map(|status| WatchItem {
+            status: Some(status),
+        });
+        let stream = futures::stream::once(async { initial })
+            .chain(changes)
+            .map(Ok);
+        Ok(Response::new(Box::pin(stream)))
+    }
+
+    async fn get_stats(
+        &self,
+        request: Request<StatsRequest>,
+    ) -> Result<Response<StatsResponse>, Status> {
+        Ok(Response::new(self.stats(&request.into_inner().vm_name)?))
+    }
+
+    async fn get_sysinfo(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<SysinfoResponse>, Status> {
+        Ok(Response::new(SysinfoResponse {
+            ghaf_version: String::from(GHAF_VERSION),
+            secure_boot: Some(false),
+            disk_encrypted: Some(false),
+            ..Default::default()
+        }))
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter_level(args.log_level.to_level_filter())
+        .init();
+
+    let scenario = match &args.scenario {
+        Some(path) => Scenario::load(path)?,
+        None => Scenario::builtin(),
+    };
+    info!(
+        "Serving {units} units, {events} events",
+        units = scenario.units.len(),
+        events = scenario.events.len(),
+    );
+
+    let admin = Arc::new(FakeService::new(scenario));
+    tokio::spawn(Arc::clone(&admin).play_events());
+
+    let router =
+        tonic::transport::Server::builder().add_service(AdminServiceServer::from_arc(admin));
+    if let Some(path) = &args.unix {
+        // A socket left behind by an earlier run would make the bind fail
+        if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            std::fs::remove_file(path)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        info!("Listening on unix://{}", path.display());
+        router
+            .serve_with_incoming(UnixListenerStream::new(listener))
+            .await?;
+    } else {
+        info!("Listening on {}", args.listen);
+        router.serve(args.listen).await?;
+    }
+    Ok(())
+}
@@ -0,0 +1,196 @@
+//! In-memory admin service playing a scenario: the units it lists, the events that follow,
+//! and the changes that start, stop, pause and resume requests make to the units.
+//!
+//! Shared by the `fake-admin` gRPC server and the in-process backend of the `mock` build.
+
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use givc_common::pb::admin::watch_item;
+use givc_common::pb::stats::StatsResponse;
+use tokio::time::Instant;
+
+use crate::prelude::*;
+use crate::scenario::{Event, Scenario, Unit, UnitTable};
+
+/// Version reported by the fake service's sysinfo.
+pub const GHAF_VERSION: &str = "0.0.0-fake";
+
+#[derive(Debug, thiserror::Error)]
+pub enum FakeError {
+    #[error("Unit {0} not found")]
+    UnitNotFound(String),
+    #[error("VM {0} not found")]
+    VmNotFound(String),
+    #[error("VM {0} is not running")]
+    VmNotRunning(String),
+}
+
+struct State {
+    units: UnitTable,
+    watchers: Vec<async_channel::Sender<watch_item::Status>>,
+}
+
+pub struct FakeService {
+    scenario: Scenario,
+    start: Instant,
+    state: Mutex<State>,
+}
+
+impl FakeService {
+    pub fn new(scenario: Scenario) -> Self {
+        let units = UnitTable::new(scenario.units.clone());
+        Self {
+            scenario,
+            start: Instant::now(),
+            state: Mutex::new(State {
+                units,
+                watchers: vec![],
+            }),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// The units as they are now. Only `fake-admin` lists units outside of a watch.
+    #[allow(dead_code)]
+    pub fn units(&self) -> Vec<Unit> {
+        self.state().units.units().to_vec()
+    }
+
+    /// The units as they are now, and a channel with every change that follows.
+    pub fn subscribe(&self) -> (Vec<Unit>, async_channel::Receiver<watch_item::Status>) {
+        let (tx, rx) = async_channel::unbounded();
+        let mut state = self.state();
+        state.watchers.push(tx);
+        (state.units.units().to_vec(), rx)
+    }
+
+    fn apply(&self, event: Event) {
+        let mut state = self.state();
+        let Some(status) = state.units.apply(event) else {
+            return;
+        };
+        info!("{status:?}");
+        state
+            .watchers
+            .retain(|watcher| watcher.try_send(status.clone()).is_ok());
+    }
+
+    fn find(&self, pred: impl Fn(&Unit) -> bool) -> Option<Unit> {
+        self.state().units.units().iter().find(|u| pred(u)).cloned()
+    }
+
+    fn unit(&self, name: &str) -> Result<Unit, FakeError> {
+        self.find(|u| u.name == name)
+            .ok_or_else(|| FakeError::UnitNotFound(name.to_owned()))
+    }
+
+    fn vm_unit(&self, vm_name: &str) -> Result<Unit, FakeError> {
+        self.find(|u| u.is_vm() && u.vm_name.as_deref() == Some(vm_name))
+            .ok_or_else(|| FakeError::VmNotFound(vm_name.to_owned()))
+    }
+
+    fn set_status(&self, name: &str, vm_status: &str) -> Result<(), FakeError> {
+        self.unit(name)?;
+        self.apply(Event::UnitStatusChanged {
+            name: name.to_owned(),
+            vm_status: vm_status.to_owned(),
+        });
+        Ok(())
+    }
+
+    /// Start a new instance of `app` in `vm_name`, named like the real ones: `app@1.service`,
+    /// `app@2.service`, ...
+    pub fn start_app(&self, app: &str, vm_name: &str, args: &[String]) -> Result<(), FakeError> {
+        let vm = self.vm_unit(vm_name)?;
+        if vm.vm_status != "Running" {
+            return Err(FakeError::VmNotRunning(vm_name.to_owned()));
+        }
+
+        let instance = (1..)
+            .map(|n| format!("{app}@{n}.service"))
+            .find(|name| self.unit(name).is_err())
+            .unwrap_or_default();
+        info!("Starting {instance} in {vm_name} with args {args:?}");
+        self.apply(Event::UnitRegistered {
+            unit: Unit {
+                name: instance,
+                description: app.to_owned(),
+                vm_status: String::from("Running"),
+                service_type: String::from("App"),
+                vm_name: Some(vm_name.to_owned()),
+                agent_name: None,
+                count: None,
+                ..vm
+            },
+        });
+        Ok(())
+    }
+
+    pub fn start_vm(&self, vm_name: &str) -> Result<(), FakeError> {
+        let vm = self.vm_unit(vm_name)?;
+        self.set_status(&vm.name, "Running")
+    }
+
+    pub fn start_service(&self, name: &str) -> Result<(), FakeError> {
+        self.set_status(name, "Running")
+    }
+
+    pub fn pause(&self, name: &str) -> Result<(), FakeError> {
+        self.set_status(name, "Paused")
+    }
+
+    pub fn resume(&self, name: &str) -> Result<(), FakeError> {
+        self.set_status(name, "Running")
+    }
+
+    pub fn stop(&self, name: &str) -> Result<(), FakeError> {
+        let unit = self.unit(name)?;
+        if unit.is_vm() {
+            // Apps and services do not survive their VM
+            let children: Vec<String> = self
+                .state()
+                .units
+                .units()
+                .iter()
+                .filter(|u| !u.is_vm() && u.vm_name == unit.vm_name)
+                .map(|u| u.name.clone())
+                .collect();
+            for child in children {
+                self.apply(Event::UnitShutdown { name: child });
+            }
+        }
+        self.apply(Event::UnitShutdown {
+            name: name.to_owned(),
+        });
+        Ok(())
+    }
+
+    pub fn stats(&self, vm_name: &str) -> Result<StatsResponse, FakeError> {
+        self.vm_unit(vm_name)?;
+        Ok(self
+            .scenario
+            .stats(vm_name, self.start.elapsed().as_secs_f64()))
+    }
+
+    /// Apply the events of the scenario as their time comes. Never returns if the scenario
+    /// has repeating events.
+    pub async fn play_events(self: Arc<Self>) {
+        for (at, event) in self.scenario.timeline() {
+            // Loaded scenarios have valid times, this is about repeats that run for ages
+            let Some(deadline) = Duration::try_from_secs_f64(at)
+                .ok()
+                .and_then(|at| self.start.checked_add(at))
+            else {
+                warn!("Scenario timeline stopped, cannot wait until {at} s");
+                return;
+            };
+            tokio::time::sleep_until(deadline).await;
+            self.apply(event);
+        }
+        debug!("Scenario timeline finished");
+    }
+}
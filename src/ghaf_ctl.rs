@@ -0,0 +1,542 @@
+//! Command-line companion of the control panel: what the GUI does, without a display.
+
+mod admin_backend;
+mod audit_log;
+mod connection_args;
+// The panel edits profiles, ghaf-ctl only reads them and records pins
+#[allow(dead_code)]
+mod connection_profiles;
+// Only the panel's dialog asks whether an address needs a port
+#[allow(dead_code)]
+mod endpoint;
+#[cfg(feature = "mock")]
+mod fake_service;
+#[cfg(feature = "mock")]
+mod scenario;
+mod server_pin;
+// ghaf-ctl only describes certificates for pinning
+#[allow(dead_code)]
+mod tls_certificates;
+
+pub mod prelude {
+    pub use log::{debug, error, info, warn};
+}
+
+use clap::{Parser, Subcommand};
+use futures::StreamExt;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, bail};
+use givc_common::query::{Event, QueryResult};
+use givc_common::types::ServiceType;
+
+use crate::admin_backend::{AdminBackend, HostSysinfoStatus, StatsResponse, givc_connector};
+use crate::audit_log::{AuditEntry, Origin, Outcome};
+use crate::connection_args::ConnectionArgs;
+use crate::connection_profiles::{ConnectionProfile, ConnectionProfiles};
+use crate::prelude::*;
+use crate::server_pin::{PinCheck, ServerPin};
+
+// How long to wait for an answer of the admin service
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Parser, Debug)]
+#[command(name = "ghaf-ctl")]
+#[command(about = "Control Ghaf VMs, apps and services from the command line", long_about = None)]
+struct Args {
+    #[command(flatten)]
+    connection: ConnectionArgs,
+
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+
+    /// Log severity
+    #[arg(long, default_value_t = log::Level::Warn)]
+    log_level: log::Level,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// List VMs, apps and services
+    List,
+    /// Print unit changes as they happen, until interrupted
+    Watch,
+    /// Show a unit, by unit name or VM name
+    Status {
+        name: String,
+    },
+    /// Start a unit, or with --vm, start the app NAME in that VM
+    Start {
+        name: String,
+        #[arg(long)]
+        vm: Option<String>,
+        /// Arguments of the app
+        #[arg(last = true)]
+        args: Vec<String>,
+    },
+    /// Stop a unit, by unit name or VM name
+    Stop {
+        name: String,
+    },
+    /// Pause a unit, by unit name or VM name
+    Pause {
+        name: String,
+    },
+    /// Resume a unit, by unit name or VM name
+    Resume {
+        name: String,
+    },
+    /// Show memory and CPU load of a VM
+    Stats {
+        vm: String,
+        /// Keep printing, every --interval seconds
+        #[arg(long)]
+        follow: bool,
+        #[arg(long, default_value_t = 1)]
+        interval: u64,
+    },
+    /// Show Ghaf version, secure boot and disk encryption state
+    Sysinfo,
+    SetLocale {
+        locale: String,
+    },
+    SetTimezone {
+        timezone: String,
+    },
+    /// Show the audit log of actions taken from this machine, oldest first
+    History,
+}
+
+#[derive(Serialize)]
+struct UnitRow {
+    name: String,
+    description: String,
+    vm_name: String,
+    service_type: String,
+    vm_type: String,
+    status: String,
+    trust_level: String,
+}
+
+impl From<&QueryResult> for UnitRow {
+    fn from(unit: &QueryResult) -> Self {
+        Self {
+            name: unit.name.clone(),
+            description: unit.description.clone(),
+            vm_name: unit.vm_name.clone().unwrap_or_default(),
+            service_type: format!("{:?}", unit.service_type),
+            vm_type: format!("{:?}", unit.vm_type),
+            status: format!("{:?}", unit.status),
+            trust_level: format!("{:?}", unit.trust_level),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct StatsRow {
+    vm_name: String,
+    memory_total: u64,
+    memory_used: u64,
+    memory_free: u64,
+    /// Percent of the CPU cycles spent in user space
+    cpu_user: f32,
+    /// Percent of the CPU cycles spent in the kernel
+    cpu_sys: f32,
+}
+
+impl StatsRow {
+    #[allow(clippy::cast_precision_loss)]
+    fn new(vm_name: &str, stats: &StatsResponse) -> Self {
+        let memory = stats.memory.clone().unwrap_or_default();
+        let process = stats.process.clone().unwrap_or_default();
+        let percent = |cycles: u64| {
+            if process.total_cycles == 0 {
+                0.0
+            } else {
+                cycles as f32 * 100.0 / process.total_cycles as f32
+            }
+        };
+        Self {
+            vm_name: vm_name.to_owned(),
+            memory_total: memory.total,
+            memory_used: memory.total.saturating_sub(memory.available),
+            memory_free: memory.free,
+            cpu_user: percent(process.user_cycles),
+            cpu_sys: percent(process.sys_cycles),
+        }
+    }
+}
+
+async fn timeout<T>(
+    future: impl Future<Output = Result<T, anyhow::Error>>,
+) -> Result<T, anyhow::Error> {
+    tokio::time::timeout(TIMEOUT, future)
+        .await
+        .with_context(|| format!("No answer within {} s", TIMEOUT.as_secs()))?
+}
+
+// The arguments only choose the scenario of the mock build
+#[cfg_attr(not(feature = "mock"), allow(unused_variables))]
+fn connect(
+    args: &ConnectionArgs,
+    profile: &ConnectionProfile,
+) -> Result<(Box<dyn AdminBackend>, Option<Arc<ServerPin>>), anyhow::Error> {
+    #[cfg(feature = "mock")]
+    let connector = Some(admin_backend::fake_connector(args.mock_scenario()?));
+    #[cfg(not(feature = "mock"))]
+    let connector = None;
+
+    let pin = profile.tofu.then(|| ServerPin::new(profile.pinned.clone()));
+    let connector = connector.unwrap_or_else(|| {
+        pin.clone()
+            .map_or_else(givc_connector, server_pin::connector)
+    });
+    let address = endpoint::parse(&profile.address, profile.port)?;
+    Ok((connector(address, profile.tls_info()), pin))
+}
+
+/// Check the admin service certificate of a trust-on-first-use profile like the panel
+/// does, recording it on first use. The channel connects on the first call, and the
+/// certificate is checked in that handshake.
+async fn check_server_pin(
+    backend: &dyn AdminBackend,
+    profile: &ConnectionProfile,
+    pin: &ServerPin,
+) -> Result<(), anyhow::Error> {
+    let connected = timeout(backend.sysinfo()).await;
+    match pin.take_check() {
+        None => {}
+        Some(PinCheck::Recorded(presented)) => {
+            info!(
+                "Recorded admin service certificate {}",
+                presented.fingerprint
+            );
+            let mut profiles = ConnectionProfiles::load()?;
+            if profiles.set_pinned(&profile.name, Some(presented)) {
+                profiles.save()?;
+            }
+        }
+        Some(PinCheck::Changed(presented)) => bail!(
+            "The admin service certificate changed from {} to {}, trust the new one in the \
+             connection dialog of the panel",
+            profile.pinned.clone().unwrap_or_default().fingerprint,
+            presented.fingerprint
+        ),
+    }
+    connected.map(drop)
+}
+
+async fn units(backend: &dyn AdminBackend) -> Result<Vec<QueryResult>, anyhow::Error> {
+    let mut units = timeout(backend.watch()).await?.initial;
+    units.sort_by_cached_key(|unit| {
+        (
+            unit.vm_name.clone(),
+            unit.service_type != ServiceType::VM,
+            unit.name.clone(),
+        )
+    });
+    Ok(units)
+}
+
+/// Find a unit by its name, or a VM by the VM name.
+async fn find_unit(backend: &dyn AdminBackend, name: &str) -> Result<QueryResult, anyhow::Error> {
+    let units = units(backend).await?;
+    units
+        .iter()
+        .find(|unit| unit.name == name)
+        .or_else(|| {
+            units.iter().find(|unit| {
+                unit.service_type == ServiceType::VM && unit.vm_name.as_deref() == Some(name)
+            })
+        })
+        .cloned()
+        .with_context(|| format!("No unit or VM named {name}"))
+}
+
+/// `chrome` for the app unit `chrome@1.service`.
+fn app_name(unit_name: &str) -> Option<&str> {
+    unit_name
+        .strip_suffix(".service")
+        .and_then(|name| name.rsplit_once('@'))
+        .and_then(|(name, number)| number.chars().all(|c| c.is_ascii_digit()).then_some(name))
+}
+
+fn print_table<const N: usize>(headers: [&str; N], rows: &[[String; N]]) {
+    let mut widths = headers.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let print_row = |cells: [&str; N]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(headers);
+    for row in rows {
+        print_row(row.each_ref().map(String::as_str));
+    }
+}
+
+fn print_json(value: &impl Serialize) -> Result<(), anyhow::Error> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn format_bytes(bytes: u64) -> String {
+    const MIB: f64 = 1024.0 * 1024.0;
+    let mib = bytes as f64 / MIB;
+    if mib >= 1024.0 {
+        format!("{:.1} GiB", mib / 1024.0)
+    } else {
+        format!("{mib:.0} MiB")
+    }
+}
+
+fn format_flag(flag: Option<bool>) -> &'static str {
+    match flag {
+        Some(true) => "enabled",
+        Some(false) => "disabled",
+        None => "unknown",
+    }
+}
+
+/// Run a control action, record it in the audit log like the GUI does, and report it.
+async fn control(
+    json: bool,
+    unit: &str,
+    vm_name: &str,
+    action: &str,
+    call: impl Future<Output = Result<(), anyhow::Error>>,
+) -> Result<(), anyhow::Error> {
+    let result = timeout(call).await;
+    let entry = AuditEntry::new(unit, vm_name, action, &result).with_origin(Origin::Cli);
+    audit_log::record(&entry);
+    if json {
+        print_json(&entry)?;
+    } else if entry.result == Outcome::Success {
+        println!("{action} {unit}: done");
+    }
+    result.with_context(|| format!("{action} {unit} failed"))
+}
+
+/// The audit log is local, it needs no connection to the admin service.
+fn history(json: bool) -> Result<(), anyhow::Error> {
+    let entries = audit_log::load();
+    if json {
+        return print_json(&entries);
+    }
+    let rows: Vec<[String; 6]> = entries
+        .into_iter()
+        .map(|entry| {
+            let result = match entry.result {
+                Outcome::Success => String::from("ok"),
+                Outcome::Failure => entry.error.unwrap_or_else(|| String::from("failed")),
+            };
+            [
+                entry.timestamp,
+                entry.action,
+                entry.service,
+                entry.vm_name,
+                String::from(entry.origin.label()),
+                result,
+            ]
+        })
+        .collect();
+    print_table(["TIME", "ACTION", "UNIT", "VM", "FROM", "RESULT"], &rows);
+    Ok(())
+}
+
+#[allow(clippy::too_many_lines)]
+async fn run(args: Args) -> Result<(), anyhow::Error> {
+    if let Command::History = args.command {
+        return history(args.json);
+    }
+    let profile = args.connection.profile()?;
+    let (backend, pin) = connect(&args.connection, &profile)?;
+    let backend = backend.as_ref();
+    if let Some(pin) = &pin {
+        check_server_pin(backend, &profile, pin).await?;
+    }
+    let json = args.json;
+
+    match args.command {
+        Command::List => {
+            let units: Vec<UnitRow> = units(backend).await?.iter().map(UnitRow::from).collect();
+            if json {
+                return print_json(&units);
+            }
+            let rows: Vec<[String; 5]> = units
+                .into_iter()
+                .map(|unit| {
+                    [
+                        unit.name,
+                        unit.service_type,
+                        unit.vm_name,
+                        unit.status,
+                        unit.trust_level,
+                    ]
+                })
+                .collect();
+            print_table(["NAME", "TYPE", "VM", "STATUS", "TRUST"], &rows);
+        }
+        Command::Watch => {
+            let mut events = timeout(backend.watch()).await?.events;
+            while let Some(event) = events.next().await {
+                let (change, unit) = match &event {
+                    Event::UnitRegistered(unit) => ("registered", unit),
+                    Event::UnitStatusChanged(unit) => ("changed", unit),
+                    Event::UnitShutdown(unit) => ("shutdown", unit),
+                };
+                let unit = UnitRow::from(unit);
+                if json {
+                    let line = serde_json::json!({ "event": change, "unit": unit });
+                    println!("{line}");
+                } else {
+                    println!(
+                        "{change:<10}  {}  {}  {}",
+                        unit.name, unit.vm_name, unit.status
+                    );
+                }
+            }
+            warn!("Admin service closed the watch");
+        }
+        Command::Status { name } => {
+            let unit = UnitRow::from(&find_unit(backend, &name).await?);
+            if json {
+                return print_json(&unit);
+            }
+            println!("Name:        {}", unit.name);
+            println!("Description: {}", unit.description);
+            println!("VM:          {}", unit.vm_name);
+            println!("Type:        {} ({})", unit.service_type, unit.vm_type);
+            println!("Status:      {}", unit.status);
+            println!("Trust level: {}", unit.trust_level);
+        }
+        Command::Start {
+            name,
+            vm: Some(vm),
+            args,
+        } => {
+            let call = async {
+                backend
+                    .start_app(name.clone(), vm.clone(), args)
+                    .await
+                    .map(drop)
+            };
+            control(json, &name, &vm, "Start", call).await?;
+        }
+        Command::Start {
+            name,
+            vm: None,
+            args,
+        } => {
+            let unit = find_unit(backend, &name).await?;
+            let vm = unit.vm_name.clone().unwrap_or_default();
+            let call = async {
+                if unit.service_type == ServiceType::VM {
+                    backend.start_vm(vm.clone()).await
+                } else if let (ServiceType::App, Some(app)) =
+                    (unit.service_type, app_name(&unit.name))
+                {
+                    backend.start_app(app.to_owned(), vm.clone(), args).await
+                } else {
+                    backend.start_service(unit.name.clone(), vm.clone()).await
+                }
+                .map(drop)
+            };
+            control(json, &unit.name, &vm, "Start", call).await?;
+        }
+        Command::Stop { name } => {
+            let unit = find_unit(backend, &name).await?;
+            let vm = unit.vm_name.unwrap_or_default();
+            let call = backend.stop(unit.name.clone());
+            control(json, &unit.name, &vm, "Shutdown", call).await?;
+        }
+        Command::Pause { name } => {
+            let unit = find_unit(backend, &name).await?;
+            let vm = unit.vm_name.unwrap_or_default();
+            let call = backend.pause(unit.name.clone());
+            control(json, &unit.name, &vm, "Pause", call).await?;
+        }
+        Command::Resume { name } => {
+            let unit = find_unit(backend, &name).await?;
+            let vm = unit.vm_name.unwrap_or_default();
+            let call = backend.resume(unit.name.clone());
+            control(json, &unit.name, &vm, "Resume", call).await?;
+        }
+        Command::Stats {
+            vm,
+            follow,
+            interval,
+        } => loop {
+            let stats = StatsRow::new(&vm, &timeout(backend.get_stats(vm.clone())).await?);
+            if json {
+                // One object per line, so that followed output can be read as JSON lines
+                println!("{}", serde_json::to_string(&stats)?);
+            } else {
+                println!(
+                    "{vm}: memory {used} / {total} used, {free} free, CPU {user:.1}% user, {sys:.1}% system",
+                    vm = stats.vm_name,
+                    used = format_bytes(stats.memory_used),
+                    total = format_bytes(stats.memory_total),
+                    free = format_bytes(stats.memory_free),
+                    user = stats.cpu_user,
+                    sys = stats.cpu_sys,
+                );
+            }
+            if !follow {
+                break;
+            }
+            tokio::time::sleep(Duration::from_secs(interval.max(1))).await;
+        },
+        Command::Sysinfo => {
+            let HostSysinfoStatus {
+                ghaf_version,
+                secure_boot,
+                disk_encryption,
+            } = timeout(backend.sysinfo()).await?;
+            if json {
+                return print_json(&serde_json::json!({
+                    "ghaf_version": ghaf_version,
+                    "secure_boot": secure_boot,
+                    "disk_encryption": disk_encryption,
+                }));
+            }
+            println!("Ghaf version:    {ghaf_version}");
+            println!("Secure boot:     {}", format_flag(secure_boot));
+            println!("Disk encryption: {}", format_flag(disk_encryption));
+        }
+        Command::SetLocale { locale } => {
+            let action = format!("SetLocale {locale}");
+            control(json, "", "", &action, backend.set_locale(locale)).await?;
+        }
+        Command::SetTimezone { timezone } => {
+            let action = format!("SetTimezone {timezone}");
+            control(json, "", "", &action, backend.set_timezone(timezone)).await?;
+        }
+        // Dispatched before connecting
+        Command::History => unreachable!(),
+    }
+    Ok(())
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), anyhow::Error> {
+    let args = Args::parse();
+    env_logger::Builder::new()
+        .filter_level(args.log_level.to_level_filter())
+        .init();
+    run(args).await
+}
@@ -0,0 +1,197 @@
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+
+use crate::audit_log::{self, AuditEntry, Origin, Outcome};
+
+mod imp {
+    use gtk::prelude::*;
+    use gtk::subclass::prelude::*;
+    use gtk::{CompositeTemplate, DropDown, ListBox, Stack, StringList, glib};
+    use std::cell::RefCell;
+
+    use crate::audit_log::AuditEntry;
+
+    #[derive(Default, CompositeTemplate)]
+    #[template(resource = "/ae/tii/ghaf/controlpanelgui/ui/history_page.ui")]
+    pub struct HistoryPage {
+        #[template_child]
+        pub vm_filter: TemplateChild<DropDown>,
+        #[template_child]
+        pub outcome_filter: TemplateChild<DropDown>,
+        #[template_child]
+        pub history_stack: TemplateChild<Stack>,
+        #[template_child]
+        pub entries_list: TemplateChild<ListBox>,
+
+        pub entries: RefCell<Vec<AuditEntry>>,
+        pub vm_names: RefCell<Option<StringList>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for HistoryPage {
+        const NAME: &'static str = "HistoryPage";
+        type Type = super::HistoryPage;
+        type ParentType = gtk::Box;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+            klass.bind_template_callbacks();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[gtk::template_callbacks]
+    impl HistoryPage {
+        #[template_callback]
+        fn on_filter_changed(&self) {
+            self.obj().apply_filter();
+        }
+
+        #[template_callback]
+        fn on_refresh_clicked(&self) {
+            self.obj().refresh();
+        }
+    } //end #[gtk::template_callbacks]
+
+    impl ObjectImpl for HistoryPage {
+        fn constructed(&self) {
+            self.parent_constructed();
+            // The log grows while the app runs, so reload it whenever the page is shown
+            self.obj().connect_map(super::HistoryPage::refresh);
+        }
+    }
+
+    impl WidgetImpl for HistoryPage {}
+    impl BoxImpl for HistoryPage {}
+}
+
+glib::wrapper! {
+pub struct HistoryPage(ObjectSubclass<imp::HistoryPage>)
+    @extends gtk::Widget, gtk::Box,
+    @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl Default for HistoryPage {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const ALL_VMS: &str = "All VMs";
+
+impl HistoryPage {
+    pub fn new() -> Self {
+        glib::Object::builder().build()
+    }
+
+    /// Reload the audit log from disk, keeping the current filter where possible.
+    pub fn refresh(&self) {
+        let imp = self.imp();
+        let entries = audit_log::load();
+
+        let mut vm_names: Vec<&str> = entries
+            .iter()
+            .map(|entry| entry.vm_name.as_str())
+            .filter(|vm_name| !vm_name.is_empty())
+            .collect();
+        vm_names.sort_unstable();
+        vm_names.dedup();
+
+        let selected_vm = self.selected_vm();
+        let model = gtk::StringList::new(&[&[ALL_VMS], vm_names.as_slice()].concat());
+        let selected = selected_vm
+            .and_then(|vm| vm_names.iter().position(|name| *name == vm))
+            .and_then(|pos| u32::try_from(pos + 1).ok())
+            .unwrap_or(0);
+
+        // The selection handlers read both, they must be up to date before the dropdown changes
+        imp.entries.replace(entries);
+        imp.vm_names.replace(Some(model.clone()));
+        imp.vm_filter.set_model(Some(&model));
+        imp.vm_filter.set_selected(selected);
+        self.apply_filter();
+    }
+
+    fn selected_vm(&self) -> Option<String> {
+        let imp = self.imp();
+        let selected = imp.vm_filter.selected();
+        if selected == 0 || selected == gtk::INVALID_LIST_POSITION {
+            return None;
+        }
+        imp.vm_names
+            .borrow()
+            .as_ref()
+            .and_then(|model| model.string(selected))
+            .map(Into::into)
+    }
+
+    fn selected_outcome(&self) -> Option<Outcome> {
+        match self.imp().outcome_filter.selected() {
+            1 => Some(Outcome::Success),
+            2 => Some(Outcome::Failure),
+            _ => None,
+        }
+    }
+
+    fn apply_filter(&self) {
+        let imp = self.imp();
+        let vm = self.selected_vm();
+        let outcome = self.selected_outcome();
+
+        imp.entries_list.remove_all();
+        let mut shown = 0;
+        for entry in imp.entries.borrow().iter().rev() {
+            if vm.as_ref().is_some_and(|vm| *vm != entry.vm_name)
+                || outcome.is_some_and(|outcome| outcome != entry.result)
+            {
+                continue;
+            }
+            imp.entries_list.append(&entry_row(entry));
+            shown += 1;
+        }
+
+        imp.history_stack
+            .set_visible_child_name(if shown == 0 { "empty" } else { "entries" });
+    }
+}
+
+fn entry_row(entry: &AuditEntry) -> adw::ActionRow {
+    let timestamp = chrono::DateTime::parse_from_rfc3339(&entry.timestamp).map_or_else(
+        |_| entry.timestamp.clone(),
+        |time| time.format("%Y-%m-%d %H:%M:%S").to_string(),
+    );
+    let title = if entry.service.is_empty() {
+        entry.action.clone()
+    } else {
+        [entry.action.as_str(), entry.service.as_str()].join(" ")
+    };
+    let mut subtitle = if entry.vm_name.is_empty() {
+        timestamp
+    } else {
+        format!("{timestamp} · {vm}", vm = entry.vm_name)
+    };
+    if entry.origin != Origin::Panel {
+        subtitle.push_str(" · via ");
+        subtitle.push_str(entry.origin.label());
+    }
+    if let Some(error) = &entry.error {
+        subtitle.push('\n');
+        subtitle.push_str(error);
+    }
+
+    let row = adw::ActionRow::builder()
+        .title(title)
+        .subtitle(subtitle)
+        .use_markup(false)
+        .build();
+    let icon = match entry.result {
+        Outcome::Success => "emblem-ok-symbolic",
+        Outcome::Failure => "dialog-error-symbolic",
+    };
+    row.add_prefix(&gtk::Image::from_icon_name(icon));
+    row
+}
@@ -0,0 +1,203 @@
+use clap::Args;
+
+/// Scheme of the links other Ghaf components use to open the panel somewhere.
+pub const LINK_SCHEME: &str = "ghaf-ctrl-panel";
+
+/// Pages that `--page` and `ghaf-ctrl-panel://page/...` accept.
+pub const PAGES: &[&str] = &[
+    "services",
+    "settings",
+    "settings/locale",
+    "settings/history",
+    "settings/about",
+];
+
+/// Where a launch takes the user. Forwarded to the running instance if there is one.
+#[derive(Args, Debug, Clone, Default)]
+pub struct LaunchArgs {
+    /// Page to show
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(PAGES))]
+    page: Option<String>,
+
+    /// Unit to select, by unit name or VM name
+    #[arg(long)]
+    select: Option<String>,
+
+    /// Link to open: `ghaf-ctrl-panel://vm/<name>`, `ghaf-ctrl-panel://unit/<name>` or
+    /// `ghaf-ctrl-panel://page/<page>`
+    #[arg(value_parser = parse_link)]
+    link: Option<Destination>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Destination {
+    pub page: Option<String>,
+    /// Unit name or VM name
+    pub select: Option<String>,
+}
+
+/// Part of the window a page name shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Page<'a> {
+    Services,
+    /// Settings, on the page with the row of that name if given. The `settings/` prefix
+    /// of such pages is optional
+    Settings(Option<&'a str>),
+}
+
+impl<'a> Page<'a> {
+    pub fn parse(page: &'a str) -> Self {
+        match page.strip_prefix("settings/").unwrap_or(page) {
+            "services" => Self::Services,
+            "settings" => Self::Settings(None),
+            name => Self::Settings(Some(name)),
+        }
+    }
+}
+
+impl Destination {
+    pub fn is_empty(&self) -> bool {
+        self.page.is_none() && self.select.is_none()
+    }
+}
+
+fn parse_link(link: &str) -> Result<Destination, String> {
+    let path = link
+        .strip_prefix(LINK_SCHEME)
+        .and_then(|rest| rest.strip_prefix("://"))
+        .ok_or_else(|| format!("not a {LINK_SCHEME}:// link"))?;
+    let (kind, target) = path
+        .trim_end_matches('/')
+        .split_once('/')
+        .ok_or_else(|| String::from("expected <kind>/<name>"))?;
+    if target.is_empty() {
+        return Err(format!("missing name after {kind}/"));
+    }
+    match kind {
+        "vm" | "unit" => Ok(Destination {
+            page: None,
+            select: Some(target.to_owned()),
+        }),
+        "page" if PAGES.contains(&target) => Ok(Destination {
+            page: Some(target.to_owned()),
+            select: None,
+        }),
+        "page" => Err(format!(
+            "unknown page {target}, expected one of {}",
+            PAGES.join(", ")
+        )),
+        _ => Err(format!(
+            "unknown link kind {kind}, expected vm, unit or page"
+        )),
+    }
+}
+
+impl LaunchArgs {
+    /// The options win over the link.
+    pub fn destination(&self) -> Destination {
+        let link = self.link.clone().unwrap_or_default();
+        Destination {
+            page: self.page.clone().or(link.page),
+            select: self.select.clone().or(link.select),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_links() {
+        let cases = [
+            (
+                "ghaf-ctrl-panel://vm/business-vm",
+                Some((None, Some("business-vm"))),
+            ),
+            (
+                "ghaf-ctrl-panel://unit/foo@1.service",
+                Some((None, Some("foo@1.service"))),
+            ),
+            (
+                "ghaf-ctrl-panel://unit/foo.service/",
+                Some((None, Some("foo.service"))),
+            ),
+            (
+                "ghaf-ctrl-panel://page/services",
+                Some((Some("services"), None)),
+            ),
+            (
+                "ghaf-ctrl-panel://page/settings/about",
+                Some((Some("settings/about"), None)),
+            ),
+            (
+                "ghaf-ctrl-panel://page/settings/about/",
+                Some((Some("settings/about"), None)),
+            ),
+            ("ghaf-ctrl-panel://page/nowhere", None),
+            ("ghaf-ctrl-panel://page/settings/nowhere", None),
+            ("ghaf-ctrl-panel://app/foo", None),
+            ("ghaf-ctrl-panel://vm", None),
+            ("ghaf-ctrl-panel://vm/", None),
+            ("ghaf-ctrl-panel://vm//", None),
+            ("ghaf-ctrl-panel://", None),
+            ("ghaf-ctrl-panel:vm/foo", None),
+            ("http://vm/foo", None),
+            ("vm/foo", None),
+            ("", None),
+        ];
+        for (link, expected) in cases {
+            let parsed = parse_link(link).ok();
+            let parsed = parsed
+                .as_ref()
+                .map(|dest| (dest.page.as_deref(), dest.select.as_deref()));
+            assert_eq!(parsed, expected, "{link}");
+        }
+    }
+
+    #[test]
+    fn pages() {
+        let cases = [
+            ("services", Page::Services),
+            ("settings", Page::Settings(None)),
+            ("settings/locale", Page::Settings(Some("locale"))),
+            ("settings/history", Page::Settings(Some("history"))),
+            ("settings/about", Page::Settings(Some("about"))),
+            ("about", Page::Settings(Some("about"))),
+            ("settings/nowhere", Page::Settings(Some("nowhere"))),
+        ];
+        for (page, expected) in cases {
+            assert_eq!(Page::parse(page), expected, "{page}");
+        }
+    }
+
+    #[test]
+    fn options_win_over_link() {
+        let link = parse_link("ghaf-ctrl-panel://vm/chrome-vm").ok();
+        let cases = [
+            (None, None, None, (None, None)),
+            (None, None, link.clone(), (None, Some("chrome-vm"))),
+            (
+                Some("settings"),
+                None,
+                link.clone(),
+                (Some("settings"), Some("chrome-vm")),
+            ),
+            (None, Some("gui-vm"), link, (None, Some("gui-vm"))),
+        ];
+        for (page, select, link, (expected_page, expected_select)) in cases {
+            let args = LaunchArgs {
+                page: page.map(str::to_owned),
+                select: select.map(str::to_owned),
+                link,
+            };
+            let destination = args.destination();
+            assert_eq!(destination.page.as_deref(), expected_page);
+            assert_eq!(destination.select.as_deref(), expected_select);
+            assert_eq!(
+                destination.is_empty(),
+                expected_page.is_none() && expected_select.is_none()
+            );
+        }
+    }
+}
@@ -1,23 +1,42 @@
+mod admin_backend;
 mod application;
+mod audit_log;
 mod about;
+mod cert_monitor;
+mod connection_args;
 mod connection_config;
+mod connection_profiles;
+mod connection_state;
 mod control_action;
 mod data_gobject;
+mod dbus_service;
+mod endpoint;
 mod error_popup;
+#[cfg(feature = "mock")]
+mod fake_service;
+mod history_page;
 mod language_region_notify_popup;
 mod language_region_settings_page;
+mod launch_args;
 mod locale_provider;
+mod notifications;
 mod plot;
+mod preferences;
 mod prelude;
+#[cfg(feature = "mock")]
+mod scenario;
 mod security_icon;
 mod serie;
+mod server_pin;
 mod service_gobject;
 mod service_model;
 mod service_row;
 mod service_settings;
 mod settings;
 mod settings_action;
+mod sort_order;
 mod status_icon;
+mod tls_certificates;
 mod typed_list_store;
 mod window;
 mod wireguard_vms;
@@ -27,18 +46,16 @@ use self::window::ControlPanelGuiWindow;
 use clap::{Parser, ValueEnum};
 use std::path::PathBuf;
 
-use givc_client::endpoint::TlsConfig;
-use gtk::gio;
 use gtk::prelude::*;
+use gtk::{gio, glib};
 use syslog::{BasicLogger, Formatter3164};
 
+use crate::connection_args::ConnectionArgs;
+use crate::launch_args::LaunchArgs;
 use crate::wireguard_vms::initialize_wvm_list;
 use env_logger::Builder;
 use prelude::*;
 
-const ADMIN_SERVICE_ADDR: &str = "192.168.101.10";
-const ADMIN_SERVICE_PORT: u16 = 9001;
-
 #[derive(ValueEnum, Default, Debug, Clone, Copy, PartialEq)]
 pub enum LogOutput {
     #[default]
@@ -50,28 +67,22 @@ pub enum LogOutput {
 #[command(name = "ctrl-panel")]
 #[command(about = "Ghaf Control Panel", long_about = None)]
 struct Args {
-    #[arg(long)]
-    addr: Option<String>,
-    #[arg(long)]
-    port: Option<u16>,
-
-    #[arg(long, env = "NAME", default_value = "admin-vm")]
-    name: String, // for TLS service name
-
-    #[arg(long, env = "CA_CERT", default_value = "/run/givc/ca-cert.pem")]
-    cacert: Option<PathBuf>,
-
-    #[arg(long, env = "HOST_CERT", default_value = "/run/givc/cert.pem")]
-    cert: Option<PathBuf>,
+    #[command(flatten)]
+    connection: ConnectionArgs,
 
-    #[arg(long, env = "HOST_KEY", default_value = "/run/givc/key.pem")]
-    key: Option<PathBuf>,
+    #[command(flatten)]
+    launch: LaunchArgs,
 
     #[arg(long, default_value = "/etc/ctrl-panel/wireguard-gui-vms.txt")]
     wireguardlist: Option<PathBuf>,
 
-    #[arg(long, default_value_t)]
-    notls: bool,
+    /// Seconds to wait for a unit to stop during restart, and for actions to take effect
+    #[arg(long, default_value_t = 60)]
+    restart_timeout: u32,
+
+    /// Warn this many days ahead of the expiry of the TLS certificates
+    #[arg(long, default_value_t = 30)]
+    cert_warning_days: u32,
 
     /// Log severity
     #[arg(long, default_value_t = log::Level::Info)]
@@ -80,6 +91,11 @@ struct Args {
     /// Log output
     #[arg(long, value_enum, default_value_t)]
     pub log_output: LogOutput,
+
+    /// Keep running, connected, when the window is closed. Starts without a window;
+    /// launching ctrl-panel again shows it
+    #[arg(long, env = "IS_SERVICE")]
+    background: bool,
 }
 
 fn initialize_logger(args: &Args) {
@@ -115,38 +131,34 @@ fn initialize_logger(args: &Args) {
     debug!("Logger initialized");
 }
 
-fn main() /*-> glib::ExitCode*/
-{
+fn main() -> glib::ExitCode {
     //std::env::set_var("RUST_BACKTRACE", "full");
     // Parse the command-line arguments
     let args = Args::parse();
     initialize_logger(&args);
 
-    let addr = if let Some(addr) = args.addr {
-        addr
-    } else {
-        String::from(ADMIN_SERVICE_ADDR)
-    };
-
-    let port = if let Some(port) = args.port {
-        port
-    } else {
-        ADMIN_SERVICE_PORT
+    #[cfg(feature = "mock")]
+    let mock_scenario = match args.connection.mock_scenario() {
+        Ok(scenario) => scenario,
+        Err(e) => {
+            error!("{e:#}");
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
     };
 
-    let tls_info = if args.notls {
-        None
-    } else {
-        Some((
-            args.name.clone(),
-            TlsConfig {
-                ca_cert_file_path: args.cacert.expect("cacert is required"),
-                cert_file_path: args.cert.expect("cert is required"),
-                key_file_path: args.key.expect("key is required"),
-                tls_name: Some(args.name),
-            },
-        ))
+    let profile = match args.connection.profile() {
+        Ok(profile) => profile,
+        Err(e) => {
+            error!("{e:#}");
+            eprintln!("{e:#}");
+            std::process::exit(1);
+        }
     };
+    // The connection is retried anyway, the files may yet be put in place
+    if let Err(e) = tls_certificates::check_profile(&profile) {
+        warn!("{e:#}");
+    }
 
     //read file with wireguard VMs
     initialize_wvm_list(&args.wireguardlist.expect("wireguard vm list file required"));
@@ -158,18 +170,44 @@ fn main() /*-> glib::ExitCode*/
     // Create a new GtkApplication. The application manages our main loop,
     // application windows, integration with the window manager/compositor, and
     // desktop features such as file opening and single-instance applications.
+    // The ID must be valid for a second launch to find the running instance.
     let app = ControlPanelGuiApplication::new(
-        "ae.tii.ghaf./controlpanelgui",
-        gio::ApplicationFlags::empty(),
-        addr,
-        port,
-        tls_info,
+        "ae.tii.ghaf.controlpanelgui",
+        gio::ApplicationFlags::HANDLES_COMMAND_LINE,
+        profile,
     );
+    app.service_model().set_restart_timeout(args.restart_timeout.max(1));
+    app.set_background(args.background);
+    app.set_cert_warning_days(args.cert_warning_days);
+    #[cfg(feature = "mock")]
+    app.service_model()
+        .set_connector(admin_backend::fake_connector(mock_scenario));
+
+    // Every launch, the first one included, ends up here in the primary instance, with
+    // the arguments of that launch
+    app.connect_command_line(|app, command_line| {
+        match Args::try_parse_from(command_line.arguments()) {
+            Ok(args) => {
+                debug!("Command line {args:?}");
+                let destination = args.launch.destination();
+                if destination.is_empty() {
+                    app.activate();
+                } else {
+                    app.open_destination(destination);
+                }
+                glib::ExitCode::SUCCESS
+            }
+            Err(e) => {
+                command_line.printerr_literal(&e.to_string());
+                glib::ExitCode::FAILURE
+            }
+        }
+    });
 
     // Run the application. This function will block until the application
     // exits. Upon return, we have our exit code to return to the shell. (This
     // is the code you see when you do `echo $?` after running a command in a
     // terminal.
-    //put empty array as args, cause we need our ones to be processed
-    app.run_with_args(&[""; 0]);
+    // The arguments are already checked above, GApplication only forwards them
+    app.run_with_args(&std::env::args().collect::<Vec<_>>())
 }
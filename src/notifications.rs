@@ -0,0 +1,127 @@
+//! Desktop notifications for VM state changes and worsening trust levels. They go through
+//! the application, so they are shown even when the window is hidden.
+
+use givc_common::query::{TrustLevel, VMStatus};
+use gtk::gio;
+use gtk::prelude::*;
+
+use crate::application::ControlPanelGuiApplication;
+use crate::control_action::ServiceOperation;
+use crate::service_gobject::ServiceGObject;
+
+fn status_label(status: VMStatus) -> &'static str {
+    match status {
+        VMStatus::Running => "running",
+        VMStatus::Paused => "paused",
+        VMStatus::PoweredOff => "powered off",
+    }
+}
+
+// Higher is worse
+fn trust_rank(trust_level: TrustLevel) -> u8 {
+    match trust_level {
+        TrustLevel::Secure => 0,
+        TrustLevel::Warning => 1,
+        TrustLevel::NotSecure => 2,
+    }
+}
+
+fn trust_label(trust_level: TrustLevel) -> &'static str {
+    match trust_level {
+        TrustLevel::Secure => "secure",
+        TrustLevel::Warning => "warning",
+        TrustLevel::NotSecure => "not secure",
+    }
+}
+
+/// Send a notification about `obj`, replacing the previous one with the same `id`. Clicking
+/// it selects the unit in the window.
+fn send(
+    app: &ControlPanelGuiApplication,
+    id: &str,
+    obj: &ServiceGObject,
+    title: &str,
+    body: &str,
+    priority: gio::NotificationPriority,
+) {
+    let notification = gio::Notification::new(title);
+    notification.set_body(Some(body));
+    notification.set_priority(priority);
+    notification
+        .set_default_action_and_target_value("app.select-service", Some(&obj.name().to_variant()));
+    let vm_name = obj.vm_name();
+    if !vm_name.is_empty() {
+        notification.add_button_with_target_value(
+            &format!("Mute {vm_name}"),
+            "app.mute-vm",
+            Some(&vm_name.to_variant()),
+        );
+    }
+    app.send_notification(Some(id), &notification);
+}
+
+/// Notify about the update of `obj` from `status` and `trust_level`, as the preferences
+/// allow. `operation` is what the panel itself was doing to the unit: a VM that powers off
+/// while the panel is not stopping it went down unexpectedly.
+pub fn service_transition(
+    app: &ControlPanelGuiApplication,
+    obj: &ServiceGObject,
+    status: VMStatus,
+    trust_level: TrustLevel,
+    operation: ServiceOperation,
+) {
+    let preferences = app.preferences();
+    if preferences.muted_vms.contains(&obj.vm_name()) {
+        return;
+    }
+    let title = obj.title();
+
+    let new_status = obj.status();
+    if obj.is_vm() && status_label(new_status) != status_label(status) {
+        let unexpected =
+            matches!(new_status, VMStatus::PoweredOff) && operation == ServiceOperation::Idle;
+        if unexpected {
+            send(
+                app,
+                &format!("status-{}", obj.name()),
+                obj,
+                &format!("{title} shut down unexpectedly"),
+                &format!(
+                    "{title} was {} and is now powered off.",
+                    status_label(status)
+                ),
+                gio::NotificationPriority::High,
+            );
+        } else if !preferences.notify_unexpected_only {
+            send(
+                app,
+                &format!("status-{}", obj.name()),
+                obj,
+                &format!("{title} is {}", status_label(new_status)),
+                &format!("{title} was {}.", status_label(status)),
+                gio::NotificationPriority::Normal,
+            );
+        }
+    }
+
+    let new_trust_level = obj.trust_level();
+    if trust_rank(new_trust_level) > trust_rank(trust_level) {
+        let priority = if matches!(new_trust_level, TrustLevel::NotSecure) {
+            gio::NotificationPriority::Urgent
+        } else {
+            gio::NotificationPriority::High
+        };
+        send(
+            app,
+            &format!("trust-{}", obj.name()),
+            obj,
+            &format!("Trust level of {title} dropped"),
+            &format!(
+                "{title} went from {} to {}.",
+                trust_label(trust_level),
+                trust_label(new_trust_level)
+            ),
+            priority,
+        );
+    }
+}
@@ -0,0 +1,53 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+
+use gtk::glib;
+
+use crate::prelude::*;
+use crate::sort_order::SortOrder;
+
+/// Per-user settings of the control panel, stored as TOML in the user config directory.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Preferences {
+    /// Shut down and restart app VMs (and their units) without asking first
+    pub skip_confirm_app_vms: bool,
+    /// VMs collapsed in the services tree
+    pub collapsed_vms: BTreeSet<String>,
+    pub sort_order: SortOrder,
+    /// Notify about VM state changes only when a VM shuts down without the panel asking it to
+    pub notify_unexpected_only: bool,
+    /// VMs whose state and trust level changes are not notified
+    pub muted_vms: BTreeSet<String>,
+}
+
+impl Preferences {
+    pub fn path() -> PathBuf {
+        glib::user_config_dir().join("ctrl-panel/preferences.toml")
+    }
+
+    pub fn load() -> Self {
+        let path = Self::path();
+        match std::fs::read_to_string(&path) {
+            Ok(content) => toml::from_str(&content).unwrap_or_else(|e| {
+                warn!("Ignoring malformed {path}: {e}", path = path.display());
+                Self::default()
+            }),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Self::default(),
+            Err(e) => {
+                warn!("Cannot read {path}: {e}", path = path.display());
+                Self::default()
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let path = Self::path();
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        std::fs::write(&path, toml::to_string(self)?)?;
+        Ok(())
+    }
+}
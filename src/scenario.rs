@@ -0,0 +1,580 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::Context;
+use givc_common::pb::admin::{QueryListItem, watch_item};
+use givc_common::pb::stats::{MemoryStats, ProcessStats, StatsResponse};
+use givc_common::query::QueryResult;
+
+use crate::prelude::*;
+
+/// Description of what a fake admin service reports: the units known at startup, the
+/// events that follow them, and the stats of each VM.
+///
+/// Scenarios are TOML files, or JSON files if the name ends in `.json`. See
+/// `scenarios/default.toml` for the format.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Scenario {
+    pub units: Vec<Unit>,
+    pub events: Vec<TimedEvent>,
+    /// Stats per VM name, VMs not listed here report `Stats::default()`
+    pub stats: BTreeMap<String, Stats>,
+}
+
+/// A unit as the admin service lists it. Field values are the strings used on the wire,
+/// e.g. `vm_status = "Running"`, `service_type = "App"`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unit {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    pub vm_status: String,
+    pub trust_level: String,
+    pub vm_type: String,
+    pub service_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vm_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub agent_name: Option<String>,
+    /// Declare this many copies of the unit, with `{n}` in the names and the description
+    /// replaced by 1, 2, ...
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimedEvent {
+    /// Seconds since the start of the scenario
+    pub at: f64,
+    /// Repeat the event with this period, in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub every: Option<f64>,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Event {
+    UnitRegistered { unit: Unit },
+    UnitStatusChanged { name: String, vm_status: String },
+    UnitShutdown { name: String },
+}
+
+/// The kind of the event and the unit it is about, to name it in errors.
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnitRegistered { unit } => write!(f, "unit-registered {}", unit.name),
+            Self::UnitStatusChanged { name, .. } => write!(f, "unit-status-changed {name}"),
+            Self::UnitShutdown { name } => write!(f, "unit-shutdown {name}"),
+        }
+    }
+}
+
+/// Stats of one VM. CPU loads are fractions of the total cycles, 0.0 to 1.0.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Stats {
+    pub memory_total: u64,
+    pub memory_available: Curve,
+    pub memory_free: Curve,
+    pub cpu_user: Curve,
+    pub cpu_sys: Curve,
+}
+
+/// A value over time: either a constant, or `[seconds, value]` points which are
+/// interpolated linearly and start over after the last one.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Curve {
+    Constant(f64),
+    Points(Vec<(f64, f64)>),
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self {
+            memory_total: 200_000_000,
+            memory_available: Curve::Constant(100_000_000.0),
+            memory_free: Curve::Constant(50_000_000.0),
+            cpu_user: Curve::Constant(0.5),
+            cpu_sys: Curve::Constant(0.25),
+        }
+    }
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Cannot read scenario {path}", path = path.display()))?;
+        let scenario = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&content).map_err(anyhow::Error::from)
+        } else {
+            toml::from_str(&content).map_err(anyhow::Error::from)
+        };
+        scenario
+            .and_then(Self::finish)
+            .with_context(|| format!("Malformed scenario {path}", path = path.display()))
+    }
+
+    /// The scenario used when none is given, a handful of VMs and apps.
+    pub fn builtin() -> Self {
+        toml::from_str(include_str!("../scenarios/default.toml"))
+            .map_err(anyhow::Error::from)
+            .and_then(Self::finish)
+            .expect("Built-in scenario is valid")
+    }
+
+    fn finish(mut self) -> Result<Self, anyhow::Error> {
+        // Shorter periods would flood the control panel with events
+        const MIN_PERIOD: f64 = 0.1;
+
+        self.units = expand(self.units);
+        for event in &self.events {
+            if let Event::UnitRegistered { unit } = &event.event {
+                anyhow::ensure!(
+                    unit.count.is_none(),
+                    "count is not supported in events ({name})",
+                    name = unit.name
+                );
+            }
+            anyhow::ensure!(
+                event.at.is_finite() && event.at >= 0.0,
+                "at must be 0 or more seconds, not {at} ({event})",
+                at = event.at,
+                event = event.event
+            );
+            if let Some(every) = event.every {
+                anyhow::ensure!(
+                    every.is_finite() && every >= MIN_PERIOD,
+                    "every must be at least {MIN_PERIOD} seconds, not {every} ({event})",
+                    event = event.event
+                );
+            }
+        }
+        for stats in self.stats.values() {
+            for curve in [
+                &stats.memory_available,
+                &stats.memory_free,
+                &stats.cpu_user,
+                &stats.cpu_sys,
+            ] {
+                curve.validate()?;
+            }
+        }
+        self.validate()?;
+        self.events.sort_by(|a, b| a.at.total_cmp(&b.at));
+        Ok(self)
+    }
+
+    /// Check that every unit converts to a `QueryResult`, so that a typo in a status or a
+    /// type shows up when the scenario is loaded rather than as a dropped event later.
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        for unit in self.known_units() {
+            unit.to_query_result()?;
+        }
+        for event in &self.events {
+            if let Event::UnitStatusChanged { name, vm_status } = &event.event {
+                let unit = self
+                    .known_units()
+                    .find(|unit| unit.name == *name)
+                    .with_context(|| format!("Scenario event refers to unknown unit {name}"))?;
+                Unit {
+                    vm_status: vm_status.clone(),
+                    ..unit.clone()
+                }
+                .to_query_result()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Units listed initially or registered later in the timeline.
+    fn known_units(&self) -> impl Iterator<Item = &Unit> {
+        let registered = self.events.iter().filter_map(|event| match &event.event {
+            Event::UnitRegistered { unit } => Some(unit),
+            _ => None,
+        });
+        self.units.iter().chain(registered)
+    }
+
+    /// The events in the order they happen, with repeating events repeated forever.
+    pub fn timeline(&self) -> Timeline<'_> {
+        Timeline {
+            pending: self
+                .events
+                .iter()
+                .map(|event| (event.at.max(0.0), event))
+                .collect(),
+        }
+    }
+
+    /// Stats of `vm_name`, `elapsed` seconds into the scenario.
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub fn stats(&self, vm_name: &str, elapsed: f64) -> StatsResponse {
+        const TOTAL_CYCLES: u64 = 1_000_000;
+
+        let stats = self.stats.get(vm_name).cloned().unwrap_or_default();
+        let total = stats.memory_total as f64;
+        let available = stats.memory_available.at(elapsed).clamp(0.0, total);
+        let free = stats.memory_free.at(elapsed).clamp(0.0, available);
+        let user = stats.cpu_user.at(elapsed).clamp(0.0, 1.0);
+        let sys = stats.cpu_sys.at(elapsed).clamp(0.0, 1.0 - user);
+        StatsResponse {
+            memory: Some(MemoryStats {
+                total: stats.memory_total,
+                available: available as u64,
+                free: free as u64,
+                ..Default::default()
+            }),
+            process: Some(ProcessStats {
+                user_cycles: (user * TOTAL_CYCLES as f64) as u64,
+                sys_cycles: (sys * TOTAL_CYCLES as f64) as u64,
+                total_cycles: TOTAL_CYCLES,
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+}
+
+/// Replace every unit that has a `count` by its copies.
+fn expand(units: Vec<Unit>) -> Vec<Unit> {
+    units
+        .into_iter()
+        .flat_map(|unit| {
+            let Some(count) = unit.count else {
+                return vec![unit];
+            };
+            (1..=count)
+                .map(|n| {
+                    let n = n.to_string();
+                    Unit {
+                        name: unit.name.replace("{n}", &n),
+                        description: unit.description.replace("{n}", &n),
+                        vm_name: unit.vm_name.as_ref().map(|vm| vm.replace("{n}", &n)),
+                        count: None,
+                        ..unit.clone()
+                    }
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// Iterator over the events of a scenario and the time they happen at, see
+/// [`Scenario::timeline`].
+pub struct Timeline<'a> {
+    pending: Vec<(f64, &'a TimedEvent)>,
+}
+
+impl Iterator for Timeline<'_> {
+    type Item = (f64, Event);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (pos, &(at, event)) = self
+            .pending
+            .iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.0.total_cmp(&b.0))?;
+        match event.every {
+            Some(every) => self.pending[pos].0 = at + every,
+            None => {
+                self.pending.remove(pos);
+            }
+        }
+        Some((at, event.event.clone()))
+    }
+}
+
+impl Curve {
+    fn validate(&self) -> Result<(), anyhow::Error> {
+        if let Self::Points(points) = self {
+            anyhow::ensure!(!points.is_empty(), "Stats curve has no points");
+            anyhow::ensure!(
+                points.windows(2).all(|pair| pair[0].0 <= pair[1].0),
+                "Stats curve points must be in time order"
+            );
+        }
+        Ok(())
+    }
+
+    pub fn at(&self, elapsed: f64) -> f64 {
+        let points = match self {
+            Self::Constant(value) => return *value,
+            Self::Points(points) => points,
+        };
+        let (Some(&(start, first)), Some(&(period, last))) = (points.first(), points.last()) else {
+            return 0.0;
+        };
+        let t = if period > 0.0 {
+            elapsed % period
+        } else {
+            elapsed
+        };
+        if t <= start {
+            return first;
+        }
+        points
+            .windows(2)
+            .find(|pair| t <= pair[1].0)
+            .map_or(last, |pair| {
+                let ((t0, v0), (t1, v1)) = (pair[0], pair[1]);
+                if t1 > t0 {
+                    v0 + (v1 - v0) * (t - t0) / (t1 - t0)
+                } else {
+                    v1
+                }
+            })
+    }
+}
+
+impl Unit {
+    pub fn to_item(&self) -> QueryListItem {
+        QueryListItem {
+            name: self.name.clone(),
+            description: self.description.clone(),
+            vm_status: self.vm_status.clone(),
+            trust_level: self.trust_level.clone(),
+            vm_type: self.vm_type.clone(),
+            service_type: self.service_type.clone(),
+            vm_name: self.vm_name.clone(),
+            agent_name: self.agent_name.clone(),
+        }
+    }
+
+    pub fn to_query_result(&self) -> Result<QueryResult, anyhow::Error> {
+        QueryResult::try_from(self.to_item())
+            .with_context(|| format!("Invalid scenario unit {name}", name = self.name))
+    }
+
+    pub fn is_vm(&self) -> bool {
+        self.service_type == "VM"
+    }
+}
+
+/// The units of a running scenario, kept up to date as its events are applied.
+#[derive(Debug, Default, Clone)]
+pub struct UnitTable(Vec<Unit>);
+
+impl UnitTable {
+    pub fn new(units: Vec<Unit>) -> Self {
+        Self(units)
+    }
+
+    pub fn units(&self) -> &[Unit] {
+        &self.0
+    }
+
+    /// Apply `event` and return the change as the admin service reports it over `Watch`.
+    /// Shut down VMs stay listed as powered off, anything else shut down is removed.
+    pub fn apply(&mut self, event: Event) -> Option<watch_item::Status> {
+        match event {
+            Event::UnitRegistered { unit } => {
+                debug!("Unit registered: {name}", name = unit.name);
+                let item = unit.to_item();
+                self.0.retain(|u| u.name != unit.name);
+                self.0.push(unit);
+                Some(watch_item::Status::Added(item))
+            }
+            Event::UnitStatusChanged { name, vm_status } => {
+                let Some(unit) = self.0.iter_mut().find(|u| u.name == name) else {
+                    warn!("Status change of unknown unit {name}");
+                    return None;
+                };
+                debug!("Unit status changed: {name} is {vm_status}");
+                unit.vm_status = vm_status;
+                Some(watch_item::Status::Updated(unit.to_item()))
+            }
+            Event::UnitShutdown { name } => {
+                let Some(pos) = self.0.iter().position(|u| u.name == name) else {
+                    warn!("Shutdown of unknown unit {name}");
+                    return None;
+                };
+                debug!("Unit shut down: {name}");
+                let item = if self.0[pos].is_vm() {
+                    let unit = &mut self.0[pos];
+                    unit.vm_status = String::from("PoweredOff");
+                    unit.to_item()
+                } else {
+                    self.0.remove(pos).to_item()
+                };
+                Some(watch_item::Status::Removed(item))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shutdown(name: &str) -> Event {
+        Event::UnitShutdown {
+            name: name.to_owned(),
+        }
+    }
+
+    fn timed(at: f64, every: Option<f64>, name: &str) -> TimedEvent {
+        TimedEvent {
+            at,
+            every,
+            event: shutdown(name),
+        }
+    }
+
+    fn unit(name: &str, vm_name: Option<&str>, count: Option<u32>) -> Unit {
+        Unit {
+            name: name.to_owned(),
+            description: format!("{name} description"),
+            vm_status: String::from("Running"),
+            trust_level: String::from("Secure"),
+            vm_type: String::from("AppVM"),
+            service_type: String::from("VM"),
+            vm_name: vm_name.map(str::to_owned),
+            agent_name: None,
+            count,
+        }
+    }
+
+    #[test]
+    #[allow(clippy::float_cmp)]
+    fn curve_at() {
+        let ramp = Curve::Points(vec![(0.0, 0.0), (10.0, 100.0)]);
+        let late_start = Curve::Points(vec![(2.0, 1.0), (4.0, 3.0)]);
+        let single = Curve::Points(vec![(0.0, 5.0)]);
+        let step = Curve::Points(vec![(0.0, 0.0), (5.0, 0.0), (5.0, 10.0), (10.0, 10.0)]);
+        let cases = [
+            (Curve::Constant(2.5), 1000.0, 2.5),
+            (Curve::Points(vec![]), 1.0, 0.0),
+            (ramp.clone(), 0.0, 0.0),
+            (ramp.clone(), 2.5, 25.0),
+            (ramp.clone(), 9.0, 90.0),
+            // Starts over after the last point
+            (ramp.clone(), 10.0, 0.0),
+            (ramp.clone(), 12.5, 25.0),
+            (ramp, 1002.5, 25.0),
+            // Holds the first value until the first point
+            (late_start.clone(), 1.0, 1.0),
+            (late_start.clone(), 3.0, 2.0),
+            (late_start, 7.0, 2.0),
+            (single.clone(), 0.0, 5.0),
+            (single, 42.0, 5.0),
+            (step.clone(), 5.0, 0.0),
+            (step.clone(), 6.0, 10.0),
+            (step, 2.5, 0.0),
+        ];
+        for (curve, elapsed, expected) in cases {
+            assert_eq!(curve.at(elapsed), expected, "{curve:?} at {elapsed}");
+        }
+    }
+
+    #[test]
+    fn curve_validate() {
+        let cases = [
+            (Curve::Constant(1.0), true),
+            (Curve::Points(vec![(0.0, 1.0)]), true),
+            (Curve::Points(vec![(0.0, 1.0), (0.0, 2.0)]), true),
+            (Curve::Points(vec![]), false),
+            (Curve::Points(vec![(2.0, 1.0), (1.0, 2.0)]), false),
+        ];
+        for (curve, valid) in cases {
+            assert_eq!(curve.validate().is_ok(), valid, "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn timeline() {
+        let cases = [
+            (vec![], vec![]),
+            (
+                vec![timed(1.0, None, "a"), timed(2.0, None, "b")],
+                vec![(1.0, "a"), (2.0, "b")],
+            ),
+            // Repeats are expanded forever, in time order with the other events
+            (
+                vec![timed(0.0, Some(2.0), "a"), timed(3.0, None, "b")],
+                vec![(0.0, "a"), (2.0, "a"), (3.0, "b"), (4.0, "a"), (6.0, "a")],
+            ),
+            (
+                vec![timed(1.0, Some(1.5), "a"), timed(0.5, Some(2.0), "b")],
+                vec![(0.5, "b"), (1.0, "a"), (2.5, "a"), (2.5, "b"), (4.0, "a")],
+            ),
+            // Events before the start happen at the start
+            (
+                vec![timed(-5.0, None, "a"), timed(0.0, None, "b")],
+                vec![(0.0, "a"), (0.0, "b")],
+            ),
+        ];
+        for (events, expected) in cases {
+            let scenario = Scenario {
+                events,
+                ..Scenario::default()
+            };
+            let timeline: Vec<_> = scenario.timeline().take(5).collect();
+            let expected: Vec<_> = expected
+                .into_iter()
+                .map(|(at, name)| (at, shutdown(name)))
+                .collect();
+            assert_eq!(timeline, expected);
+        }
+    }
+
+    #[test]
+    fn finish_checks_times() {
+        let cases = [
+            (timed(0.0, None, "a"), true),
+            (timed(2.5, Some(0.1), "a"), true),
+            (timed(-1.0, None, "a"), false),
+            (timed(f64::NAN, None, "a"), false),
+            (timed(f64::INFINITY, None, "a"), false),
+            (timed(0.0, Some(0.0), "a"), false),
+            (timed(0.0, Some(0.01), "a"), false),
+            (timed(0.0, Some(-2.0), "a"), false),
+            (timed(0.0, Some(f64::NAN), "a"), false),
+            (timed(0.0, Some(f64::INFINITY), "a"), false),
+        ];
+        for (event, valid) in cases {
+            let scenario = Scenario {
+                events: vec![event.clone()],
+                ..Scenario::default()
+            };
+            match scenario.finish() {
+                Ok(_) => assert!(valid, "{event:?} is accepted"),
+                Err(e) => {
+                    assert!(!valid, "{event:?} is rejected: {e}");
+                    assert!(e.to_string().contains("unit-shutdown a"), "{e}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn expand_count() {
+        let expanded = expand(vec![
+            unit("vm-{n}", Some("vm-{n}"), Some(2)),
+            unit("single", None, None),
+            unit("none-{n}", None, Some(0)),
+        ]);
+        let names: Vec<_> = expanded
+            .iter()
+            .map(|unit| (unit.name.as_str(), unit.vm_name.as_deref(), unit.count))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("vm-1", Some("vm-1"), None),
+                ("vm-2", Some("vm-2"), None),
+                ("single", None, None),
+            ]
+        );
+        assert_eq!(expanded[1].description, "vm-2 description");
+    }
+}
@@ -0,0 +1,238 @@
+//! Trust on first use of the admin service certificate. Connections of such profiles check
+//! the certificate in their own TLS handshake against the one recorded for the profile,
+//! in place of the CA, and record it on the first connection. The givc client cannot be
+//! given a verifier, so these connections go through a channel of their own.
+
+use anyhow::Context;
+use sha2::{Digest, Sha256};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use givc_client::endpoint::TlsConfig;
+use givc_common::address::EndpointAddress;
+use hyper_util::rt::TokioIo;
+use rustls::client::danger::{HandshakeSignatureValid, ServerCertVerified, ServerCertVerifier};
+use rustls::crypto::{CryptoProvider, ring};
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName, UnixTime};
+use rustls::{CertificateError, ClientConfig, DigitallySignedStruct, SignatureScheme};
+use tonic::transport::{Channel, Endpoint};
+
+use crate::admin_backend::{ChannelClient, Connector, givc_connector};
+use crate::connection_profiles::PinnedCertificate;
+use crate::prelude::*;
+use crate::tls_certificates::{CertificateInfo, TIME_FORMAT};
+
+/// What the check of the admin service certificate found, if worth telling.
+#[derive(Debug)]
+pub enum PinCheck {
+    /// First connection, the certificate is to be recorded
+    Recorded(PinnedCertificate),
+    /// The certificate is not the recorded one, the connection was refused
+    Changed(PinnedCertificate),
+}
+
+/// The certificate the connections of a trust-on-first-use profile accept, and what their
+/// handshakes found.
+#[derive(Debug, Default)]
+pub struct ServerPin {
+    pinned: Mutex<Option<PinnedCertificate>>,
+    found: Mutex<Option<PinCheck>>,
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+impl ServerPin {
+    /// Accept `pinned`, or the first certificate presented if there is none yet.
+    pub fn new(pinned: Option<PinnedCertificate>) -> Arc<Self> {
+        Arc::new(Self {
+            pinned: Mutex::new(pinned),
+            found: Mutex::default(),
+        })
+    }
+
+    /// What the handshakes so far found, once.
+    pub fn take_check(&self) -> Option<PinCheck> {
+        lock(&self.found).take()
+    }
+
+    fn verify(&self, der: &[u8]) -> Result<(), rustls::Error> {
+        let presented = describe(der).map_err(|e| {
+            warn!("Cannot read the admin service certificate: {e:#}");
+            rustls::Error::InvalidCertificate(CertificateError::BadEncoding)
+        })?;
+        let mut pinned = lock(&self.pinned);
+        match &*pinned {
+            Some(pinned) if pinned.fingerprint == presented.fingerprint => Ok(()),
+            Some(_) => {
+                *lock(&self.found) = Some(PinCheck::Changed(presented));
+                Err(rustls::Error::InvalidCertificate(
+                    CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+            None => {
+                // Later handshakes of the same connection must see the same one
+                *pinned = Some(presented.clone());
+                *lock(&self.found) = Some(PinCheck::Recorded(presented));
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Trusts the certificate `ServerPin` accepts, whatever the CA. The handshake signatures
+/// are still checked, so the server has the key of what it presents.
+#[derive(Debug)]
+struct PinVerifier {
+    provider: Arc<CryptoProvider>,
+    pin: Arc<ServerPin>,
+}
+
+impl ServerCertVerifier for PinVerifier {
+    fn verify_server_cert(
+        &self,
+        end_entity: &CertificateDer<'_>,
+        _intermediates: &[CertificateDer<'_>],
+        _server_name: &ServerName<'_>,
+        _ocsp_response: &[u8],
+        _now: UnixTime,
+    ) -> Result<ServerCertVerified, rustls::Error> {
+        self.pin.verify(end_entity)?;
+        Ok(ServerCertVerified::assertion())
+    }
+
+    fn verify_tls12_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls12_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn verify_tls13_signature(
+        &self,
+        message: &[u8],
+        cert: &CertificateDer<'_>,
+        dss: &DigitallySignedStruct,
+    ) -> Result<HandshakeSignatureValid, rustls::Error> {
+        rustls::crypto::verify_tls13_signature(
+            message,
+            cert,
+            dss,
+            &self.provider.signature_verification_algorithms,
+        )
+    }
+
+    fn supported_verify_schemes(&self) -> Vec<SignatureScheme> {
+        self.provider
+            .signature_verification_algorithms
+            .supported_schemes()
+    }
+}
+
+fn fingerprint(der: &[u8]) -> String {
+    Sha256::digest(der)
+        .iter()
+        .map(|byte| format!("{byte:02X}"))
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn describe(der: &[u8]) -> Result<PinnedCertificate, anyhow::Error> {
+    let info = CertificateInfo::from_der(der)?;
+    Ok(PinnedCertificate {
+        fingerprint: fingerprint(der),
+        subject: info.subject,
+        issuer: info.issuer,
+        not_after: info.not_after.format(TIME_FORMAT).to_string(),
+    })
+}
+
+/// TLS settings that check the server certificate against `pin`. The client certificate
+/// of `tls_config` is offered if there is one, devices in bring-up may not have it yet.
+fn client_config(
+    tls_config: &TlsConfig,
+    pin: Arc<ServerPin>,
+) -> Result<ClientConfig, anyhow::Error> {
+    let provider = Arc::new(ring::default_provider());
+    let builder = ClientConfig::builder_with_provider(Arc::clone(&provider))
+        .with_safe_default_protocol_versions()?
+        .dangerous()
+        .with_custom_certificate_verifier(Arc::new(PinVerifier { provider, pin }));
+    let mut config = if tls_config.cert_file_path.exists() {
+        let certs = CertificateDer::pem_file_iter(&tls_config.cert_file_path)
+            .and_then(Iterator::collect)
+            .with_context(|| format!("Cannot read {}", tls_config.cert_file_path.display()))?;
+        let key = PrivateKeyDer::from_pem_file(&tls_config.key_file_path)
+            .with_context(|| format!("Cannot read {}", tls_config.key_file_path.display()))?;
+        builder.with_client_auth_cert(certs, key)?
+    } else {
+        debug!(
+            "No client certificate {}, connecting without",
+            tls_config.cert_file_path.display()
+        );
+        builder.with_no_client_auth()
+    };
+    config.alpn_protocols = vec![b"h2".to_vec()];
+    Ok(config)
+}
+
+/// A channel to the admin service at `host`:`port` whose TLS handshakes check the
+/// certificate against `pin`. It connects on the first call, and again when the
+/// connection is lost.
+fn channel(
+    host: String,
+    port: u16,
+    tls_name: &str,
+    tls_config: &TlsConfig,
+    pin: Arc<ServerPin>,
+) -> Channel {
+    // Setup errors are reported by every connection attempt, like connection errors
+    let tls = client_config(tls_config, pin)
+        .and_then(|config| {
+            let name = if tls_name.is_empty() {
+                host.as_str()
+            } else {
+                tls_name
+            };
+            Ok((Arc::new(config), ServerName::try_from(name)?.to_owned()))
+        })
+        .map_err(|e| format!("{e:#}"));
+    let connector = tower::service_fn(move |_: http::Uri| {
+        let host = host.clone();
+        let tls = tls.clone();
+        async move {
+            let (config, server_name) = tls.map_err(std::io::Error::other)?;
+            let stream = tokio::net::TcpStream::connect((host.as_str(), port)).await?;
+            let stream = tokio_rustls::TlsConnector::from(config)
+                .connect(server_name, stream)
+                .await?;
+            Ok::<_, std::io::Error>(TokioIo::new(stream))
+        }
+    });
+    // The connector does not look at the URI, it only names the service in requests
+    Endpoint::from_static("http://admin-service").connect_with_connector_lazy(connector)
+}
+
+/// Connect to TCP addresses through channels that trust the certificate `pin` accepts
+/// instead of the CA. Unix and vsock sockets are local, they are not pinned and go through
+/// the givc client.
+pub fn connector(pin: Arc<ServerPin>) -> Connector {
+    Arc::new(move |address, tls_info| {
+        if let (EndpointAddress::Tcp { addr, port }, Some((tls_name, tls_config))) =
+            (&address, &tls_info)
+        {
+            let channel = channel(addr.clone(), *port, tls_name, tls_config, Arc::clone(&pin));
+            return Box::new(ChannelClient::new(channel));
+        }
+        debug!("Not pinning the certificate of a local socket");
+        givc_connector()(address, tls_info)
+    })
+}
@@ -1,14 +1,16 @@
 use gtk::glib::{self, Object};
 use gtk::prelude::*;
+use gtk::subclass::prelude::*;
 
 use givc_common::query::{QueryResult, TrustLevel, VMStatus};
 use givc_common::types::{ServiceType, VmType};
 
+use crate::control_action::ServiceOperation;
 use crate::prelude::*;
 use crate::wireguard_vms::static_contains;
 
 mod imp {
-    use gtk::glib::{self, Properties};
+    use gtk::glib::{self, Properties, SourceId};
     use gtk::prelude::*;
     use gtk::subclass::prelude::*;
     use std::cell::RefCell;
@@ -16,6 +18,8 @@ mod imp {
     use givc_common::query::{TrustLevel, VMStatus};
     use givc_common::types::VmType;
 
+    use crate::control_action::ServiceOperation;
+
     pub struct ServiceData {
         pub name: String,         //unique service name, used as id
         pub display_name: String, //user-friendly name
@@ -27,6 +31,7 @@ mod imp {
         pub status: VMStatus,
         pub trust_level: TrustLevel,
         pub has_wireguard: bool,
+        pub operation: ServiceOperation,
     }
 
     impl Default for ServiceData {
@@ -42,6 +47,7 @@ mod imp {
                 status: VMStatus::default(),
                 trust_level: TrustLevel::default(),
                 has_wireguard: false,
+                operation: ServiceOperation::Idle,
             }
         }
     }
@@ -59,7 +65,10 @@ mod imp {
         #[property(name = "status", get, set, type = VMStatus, member = status, builder(VMStatus::default()))]
         #[property(name = "trust-level", get, set, type = TrustLevel, member = trust_level, builder(TrustLevel::default()))]
         #[property(name = "has-wireguard", get, set, type = bool, member = has_wireguard)]
+        #[property(name = "operation", get, set, type = ServiceOperation, member = operation, builder(ServiceOperation::default()))]
         pub data: RefCell<ServiceData>,
+
+        pub operation_timeout: RefCell<Option<SourceId>>,
     }
 
     #[glib::object_subclass]
@@ -135,16 +144,72 @@ impl ServiceGObject {
     pub fn update(&self, query_result: QueryResult) {
         self.set_property("details", query_result.description);
         self.set_property("status", query_result.status);
+        self.set_property("trust-level", query_result.trust_level);
+        // Other updates, such as a trust level refresh, may come in while the operation
+        // is still under way
+        if self.operation().is_done(self.status()) {
+            self.end_operation();
+        }
+    }
+
+    /// Mark the unit as busy with `operation` until it reaches the status the operation
+    /// leads to, until the operation fails, or until it times out after `timeout`.
+    pub fn begin_operation(&self, operation: ServiceOperation, timeout: std::time::Duration) {
+        let source = glib::timeout_add_local_once(
+            timeout,
+            glib::clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move || {
+                    obj.imp().operation_timeout.borrow_mut().take();
+                    warn!(
+                        "{name}: {operation:?} timed out",
+                        name = obj.name(),
+                        operation = obj.operation()
+                    );
+                    obj.set_operation(ServiceOperation::Idle);
+                }
+            ),
+        );
+        if let Some(old) = self.imp().operation_timeout.replace(Some(source)) {
+            old.remove();
+        }
+        self.set_operation(operation);
+    }
+
+    pub fn end_operation(&self) {
+        if let Some(source) = self.imp().operation_timeout.take() {
+            source.remove();
+        }
+        if self.operation() != ServiceOperation::Idle {
+            self.set_operation(ServiceOperation::Idle);
+        }
     }
 
     pub fn is_vm_running(&self) -> bool {
         self.is_vm() && matches!(self.status(), VMStatus::Running)
     }
 
+    /// Whether the user may run control actions on the unit. System VMs and the host are
+    /// off limits.
+    pub fn is_controllable(&self) -> bool {
+        !matches!(self.vm_type(), VmType::AdmVM | VmType::SysVM | VmType::Host)
+    }
+
     pub fn is_service(&self) -> bool {
         !self.is_vm() && !self.is_app()
     }
 
+    /// User-facing name: the display name if there is one, the unit name otherwise.
+    pub fn title(&self) -> String {
+        let display_name = self.display_name();
+        if display_name.is_empty() {
+            self.name()
+        } else {
+            display_name
+        }
+    }
+
     pub fn sort_key(&self) -> (bool, String, bool, String) {
         let vm_name = self.vm_name();
         (
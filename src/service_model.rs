@@ -1,49 +1,59 @@
-pub use givc_client::client::StatsResponse;
-pub use givc_common::pb::admin::StartResponse;
+pub use crate::admin_backend::{HostSysinfoStatus, StartResponse, StatsResponse};
 
+use anyhow::Context;
 use givc_client::endpoint::TlsConfig;
+use givc_common::query::{TrustLevel, VMStatus};
 use glib::subclass::prelude::*;
-use gtk::{self, gio, glib};
+use glib::{Object, SignalHandlerId};
+use gtk::{self, gio, glib, prelude::*};
 
+use crate::admin_backend::Connector;
+use crate::connection_profiles::PinnedCertificate;
+use crate::connection_state::ConnectionState;
+use crate::control_action::ServiceOperation;
 use crate::prelude::*;
 use crate::service_gobject::ServiceGObject;
 
-#[derive(Debug, Clone)]
-pub struct HostSysinfoStatus {
-    pub ghaf_version: String,
-    pub secure_boot: Option<bool>,
-    pub disk_encryption: Option<bool>,
-}
-
 mod imp {
-    #![cfg_attr(feature = "mock", allow(unused_imports, dead_code))]
-
     use std::cell::{Cell, RefCell};
+    use std::sync::OnceLock;
     use std::thread;
     use std::time::Duration;
 
     use anyhow::Context;
     use async_channel::Sender;
+    use futures::StreamExt;
     use gio::{ListModel, subclass::prelude::*};
     use givc_client::endpoint::TlsConfig;
-    use givc_client::{self, AdminClient};
-    use givc_common::{address::EndpointAddress, query::Event};
+    use givc_common::query::{Event, QueryResult, TrustLevel, VMStatus};
     use glib::JoinHandle;
+    use glib::subclass::Signal;
     use glib::{Object, Properties, SourceId};
     use gtk::{gio, glib, prelude::*};
     use tokio::runtime::Builder;
 
     use super::{HostSysinfoStatus, StartResponse, StatsResponse};
 
+    use crate::admin_backend::{AdminBackend, Connector, givc_connector};
+    use crate::connection_profiles::{PinnedCertificate, TOFU_NEEDS_TLS};
+    use crate::connection_state::ConnectionState;
+    use crate::control_action::ServiceOperation;
+    use crate::endpoint;
     use crate::prelude::*;
+    use crate::server_pin::{self, PinCheck, ServerPin};
     use crate::service_gobject::ServiceGObject;
+    use crate::tls_certificates;
 
     type TaskSender = Sender<(Task, Sender<Response>)>;
 
+    // Reconnect backoff bounds, in seconds
+    const RECONNECT_BACKOFF_MIN: u32 = 1;
+    const RECONNECT_BACKOFF_MAX: u32 = 60;
+
     #[derive(Default, Properties)]
     #[properties(wrapper_type = super::ServiceModel)]
     pub struct ServiceModel {
-        services: RefCell<Vec<ServiceGObject>>,
+        pub(super) services: RefCell<Vec<ServiceGObject>>,
 
         #[property(set = ServiceModel::set_address, get)]
         address: RefCell<String>,
@@ -51,11 +61,34 @@ mod imp {
         #[property(set = ServiceModel::set_port, get = ServiceModel::get_port, type = u32)]
         port: Cell<u16>,
 
+        #[property(get, builder(ConnectionState::default()))]
+        connection_state: Cell<ConnectionState>,
+
+        #[property(get)]
+        last_error: RefCell<String>,
+
+        // Seconds left until the next automatic reconnect attempt, 0 if none is scheduled
+        #[property(get)]
+        retry_in: Cell<u32>,
+
+        // How long a restart waits for the unit to power off before giving up, in seconds
+        #[property(get, set, construct, minimum = 1, default = 60)]
+        restart_timeout: Cell<u32>,
+
         reconnect_timeout: RefCell<Option<SourceId>>,
+        retry_attempt: Cell<u32>,
+        retry_handle: RefCell<Option<JoinHandle<()>>>,
+        // Bumped on every connection attempt, the list is reset with each
+        pub(super) generation: Cell<u64>,
         tls_info: RefCell<Option<(String, TlsConfig)>>,
+        // Trust on first use: check the admin service certificate against `pinned` before
+        // connecting, record it if there is none yet
+        tofu: Cell<bool>,
+        pinned: RefCell<Option<PinnedCertificate>>,
         task_runner: RefCell<Option<TaskSender>>,
-        #[cfg(not(feature = "mock"))]
         join_handle: RefCell<Option<JoinHandle<()>>>,
+        // Creates the backend on each connection, the givc client unless set otherwise
+        connector: RefCell<Option<Connector>>,
     }
 
     impl ListModelImpl for ServiceModel {
@@ -86,7 +119,42 @@ mod imp {
 
     // Trait shared by all GObjects
     #[glib::derived_properties]
-    impl ObjectImpl for ServiceModel {}
+    impl ObjectImpl for ServiceModel {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: OnceLock<[Signal; 4]> = OnceLock::new();
+            SIGNALS.get_or_init(|| {
+                [
+                    // Emitted after a unit's status was updated from the admin service
+                    Signal::builder("service-changed")
+                        .param_types([ServiceGObject::static_type()])
+                        .build(),
+                    // Same, with the status and trust level from before the update and the
+                    // operation that was in flight on the unit
+                    Signal::builder("service-transition")
+                        .param_types([
+                            ServiceGObject::static_type(),
+                            VMStatus::static_type(),
+                            TrustLevel::static_type(),
+                            ServiceOperation::static_type(),
+                        ])
+                        .build(),
+                    // Emitted when the certificate of the admin service was seen for the
+                    // first time
+                    Signal::builder("server-certificate-recorded")
+                        .param_types([PinnedCertificate::static_type()])
+                        .build(),
+                    // Emitted with the recorded and the presented certificate when they
+                    // differ. The connection is refused.
+                    Signal::builder("server-certificate-changed")
+                        .param_types([
+                            PinnedCertificate::static_type(),
+                            PinnedCertificate::static_type(),
+                        ])
+                        .build(),
+                ]
+            })
+        }
+    }
 
     #[derive(Debug)]
     pub enum Response {
@@ -183,7 +251,7 @@ mod imp {
 
     type Task = Box<
         dyn for<'a> FnOnce(
-                &'a AdminClient,
+                &'a dyn AdminBackend,
             )
                 -> std::pin::Pin<Box<dyn std::future::Future<Output = Response> + 'a>>
             + Sync
@@ -191,24 +259,16 @@ mod imp {
     >;
 
     impl ServiceModel {
-        #[cfg(not(feature = "mock"))]
         pub(super) async fn get_sysinfo_status_from_host(
             &self,
         ) -> Result<HostSysinfoStatus, anyhow::Error> {
             debug!("ServiceModel: querying host sysinfo status via admin RPC");
-            self.client_cmd(async move |client| {
-                let status = client.sysinfo().await?;
-                Ok(HostSysinfoStatus {
-                    ghaf_version: status.ghaf_version,
-                    secure_boot: status.secure_boot,
-                    disk_encryption: status.disk_encrypted,
-                })
-            })
-            .await
+            self.client_cmd(async |client| client.sysinfo().await).await
         }
 
         pub fn delayed_reconnect(&self) {
             let delay = std::time::Duration::from_millis(100);
+            self.retry_attempt.set(0);
             let mut guard = self.reconnect_timeout.borrow_mut();
             let model = self.obj().clone();
 
@@ -227,7 +287,7 @@ mod imp {
             task: T,
             cb: impl Fn(Result<R, anyhow::Error>) + 'static,
         ) where
-            T: AsyncFnOnce(&AdminClient) -> Result<R, anyhow::Error> + Send + Sync + 'static,
+            T: AsyncFnOnce(&dyn AdminBackend) -> Result<R, anyhow::Error> + Send + Sync + 'static,
             Result<R, anyhow::Error>: Into<Response>,
             R: std::convert::TryFrom<Response, Error = anyhow::Error> + 'static,
         {
@@ -243,7 +303,7 @@ mod imp {
 
         pub(super) async fn client_cmd<T, R>(&self, task: T) -> Result<R, anyhow::Error>
         where
-            T: AsyncFnOnce(&AdminClient) -> Result<R, anyhow::Error> + Send + Sync + 'static,
+            T: AsyncFnOnce(&dyn AdminBackend) -> Result<R, anyhow::Error> + Send + Sync + 'static,
             Result<R, anyhow::Error>: Into<Response>,
             R: std::convert::TryFrom<Response, Error = anyhow::Error>,
         {
@@ -256,7 +316,7 @@ mod imp {
                 .context("Not connected")?;
 
             tr.send((
-                Box::new(|client: &AdminClient| {
+                Box::new(|client: &dyn AdminBackend| {
                     let task = task(client);
                     Box::pin(async move { task.await.into() })
                 }),
@@ -266,6 +326,57 @@ mod imp {
             res_rx.recv().await?.try_into()
         }
 
+        fn set_connection_state(&self, state: ConnectionState, error: Option<String>) {
+            let error = error.unwrap_or_default();
+            if *self.last_error.borrow() != error {
+                *self.last_error.borrow_mut() = error;
+                self.obj().notify_last_error();
+            }
+            if self.connection_state.replace(state) != state {
+                debug!("Connection state: {state:?}");
+                self.obj().notify_connection_state();
+            }
+        }
+
+        fn set_retry_in(&self, seconds: u32) {
+            if self.retry_in.replace(seconds) != seconds {
+                self.obj().notify_retry_in();
+            }
+        }
+
+        fn cancel_retry(&self) {
+            if let Some(handle) = self.retry_handle.borrow_mut().take() {
+                handle.abort();
+            }
+            self.set_retry_in(0);
+        }
+
+        fn schedule_retry(&self) {
+            let attempt = self.retry_attempt.get();
+            self.retry_attempt.set(attempt.saturating_add(1));
+            let delay = RECONNECT_BACKOFF_MIN
+                .saturating_mul(1 << attempt.min(6))
+                .min(RECONNECT_BACKOFF_MAX);
+            info!("Reconnecting in {delay} s (attempt {})", attempt + 1);
+
+            let handle = glib::spawn_future_local(glib::clone!(
+                #[weak(rename_to = model)]
+                self.obj(),
+                async move {
+                    for remaining in (1..=delay).rev() {
+                        model.imp().set_retry_in(remaining);
+                        glib::timeout_future_seconds(1).await;
+                    }
+                    model.imp().set_retry_in(0);
+                    model.imp().retry_handle.borrow_mut().take();
+                    model.imp().reconnect().await;
+                }
+            ));
+            if let Some(old) = self.retry_handle.borrow_mut().replace(handle) {
+                old.abort();
+            }
+        }
+
         fn set_address(&self, address: String) {
             *self.address.borrow_mut() = address;
             self.delayed_reconnect();
@@ -281,11 +392,10 @@ mod imp {
         }
 
         #[allow(clippy::cast_possible_truncation)]
-        fn extend<T>(&self, iter: impl IntoIterator<Item = T>)
+        pub(super) fn extend<T>(&self, iter: impl IntoIterator<Item = T>)
         where
             ServiceGObject: From<T>,
         {
-            use givc_common::query::{TrustLevel, VMStatus};
             use givc_common::types::{ServiceType, VmType};
 
             let n = self.services.borrow().len();
@@ -323,60 +433,6 @@ mod imp {
             }
         }
 
-        #[cfg(feature = "mock")]
-        fn fill_by_mock_data(&self) {
-            use givc_common::query::{TrustLevel, VMStatus};
-            use givc_common::types::{ServiceType, VmType};
-
-            self.extend([
-                ServiceGObject::new(
-                    "microvm@zathura-vm.service",
-                    "This is the file.pdf and very very long description",
-                    VMStatus::Running,
-                    TrustLevel::NotSecure,
-                    ServiceType::VM,
-                    Some("zathura-vm"),
-                    VmType::AppVM,
-                ),
-                ServiceGObject::new(
-                    "zathura@1.service",
-                    "Zathura",
-                    VMStatus::Paused,
-                    TrustLevel::Secure,
-                    ServiceType::App,
-                    Some("zathura-vm"),
-                    VmType::AppVM,
-                ),
-                ServiceGObject::new(
-                    "chrome@1.service",
-                    "Google Chrome",
-                    VMStatus::Paused,
-                    TrustLevel::Secure,
-                    ServiceType::App,
-                    Some("TestVM"),
-                    VmType::AppVM,
-                ),
-                ServiceGObject::new(
-                    "appflowy@1.service",
-                    "AppFlowy",
-                    VMStatus::Running,
-                    TrustLevel::Secure,
-                    ServiceType::Svc,
-                    Some("appflowy-vm"),
-                    VmType::AppVM,
-                ),
-                ServiceGObject::new(
-                    "microvm@admin-vm.service",
-                    "AdminVM",
-                    VMStatus::Running,
-                    TrustLevel::Secure,
-                    ServiceType::VM,
-                    Some("admin-vm"),
-                    VmType::AdmVM,
-                ),
-            ]);
-        }
-
         pub(super) fn find(
             &self,
             pred: impl Fn(&ServiceGObject) -> bool,
@@ -388,74 +444,81 @@ mod imp {
                 .find_map(|(pos, obj)| pred(obj).then(|| (pos, obj.clone())))
         }
 
-        pub(super) fn set_tls_info(&self, name: String, config: TlsConfig) {
-            *self.tls_info.borrow_mut() = Some((name, config));
+        pub(super) fn target_name(&self) -> String {
+            self.tls_info
+                .borrow()
+                .as_ref()
+                .map_or_else(|| self.address.borrow().clone(), |(name, _)| name.clone())
+        }
+
+        pub(super) fn set_tls_info(&self, tls_info: Option<(String, TlsConfig)>) {
+            *self.tls_info.borrow_mut() = tls_info;
             self.delayed_reconnect();
         }
 
-        #[cfg(feature = "mock")]
-        #[allow(clippy::unused_async)]
-        async fn reconnect(&self) {
-            use givc_common::query::{TrustLevel, VMStatus};
-            use givc_common::types::{ServiceType, VmType};
-            self.fill_by_mock_data();
+        pub(super) fn set_server_pin(&self, tofu: bool, pinned: Option<PinnedCertificate>) {
+            self.tofu.set(tofu);
+            *self.pinned.borrow_mut() = pinned;
+            self.delayed_reconnect();
+        }
 
-            glib::spawn_future_local(glib::clone!(
-                #[strong(rename_to = model)]
-                self.obj(),
-                async move {
-                    glib::timeout_future_seconds(3).await;
-                    model.imp().extend(Some(ServiceGObject::new(
-                        "microvm@appflowy-vm.service",
-                        "AppFlow VM",
-                        VMStatus::Running,
-                        TrustLevel::NotSecure,
-                        ServiceType::VM,
-                        Some("appflowy-vm"),
-                        VmType::AppVM,
-                    )));
-                    glib::timeout_future_seconds(3).await;
-                    model.imp().extend(Some(ServiceGObject::new(
-                        "zathura@2.service",
-                        "Zathura",
-                        VMStatus::Paused,
-                        TrustLevel::Secure,
-                        ServiceType::App,
-                        Some("zathura-vm"),
-                        VmType::AppVM,
-                    )));
-                    glib::timeout_future_seconds(3).await;
-                    model.imp().extend(Some(ServiceGObject::new(
-                        "givc-appflowy-vm.service",
-                        "Zathura agent",
-                        VMStatus::Running,
-                        TrustLevel::Secure,
-                        ServiceType::Mgr,
-                        None,
-                        VmType::AppVM,
-                    )));
-                }
-            ));
+        pub(super) fn set_connector(&self, connector: Connector) {
+            *self.connector.borrow_mut() = Some(connector);
+            self.delayed_reconnect();
         }
 
-        #[cfg(not(feature = "mock"))]
-        #[allow(clippy::cast_possible_truncation)]
+        #[allow(clippy::cast_possible_truncation, clippy::too_many_lines)]
         async fn reconnect(&self) {
+            self.cancel_retry();
+            // Every attempt gets its own generation, so that a superseded connection
+            // does not touch the state or schedule retries when its stream ends.
+            let generation = self.generation.get().wrapping_add(1);
+            self.generation.set(generation);
+
             let _ = self.task_runner.borrow_mut().take();
             let join = self.join_handle.borrow_mut().take();
             if let Some(join) = join {
                 let _ = join.await;
             }
-            if self.address.borrow().is_empty() || self.port.get() == 0 {
+            if self.generation.get() != generation {
+                return;
+            }
+            let address = self.address.borrow().clone();
+            if address.is_empty() || (self.port.get() == 0 && !endpoint::is_socket(&address)) {
+                self.set_connection_state(ConnectionState::Disconnected, None);
                 return;
             }
-            let address = EndpointAddress::Tcp {
-                addr: self.address.borrow().clone(),
-                port: self.port.get(),
+            let address = match endpoint::parse(&address, self.port.get()) {
+                Ok(address) => address,
+                Err(e) => {
+                    self.set_connection_state(ConnectionState::Failed, Some(format!("{e:#}")));
+                    return;
+                }
             };
+            if self.tofu.get() && self.tls_info.borrow().is_none() {
+                // Not retried, there is nothing to pin until the profile changes
+                self.set_connection_state(
+                    ConnectionState::Failed,
+                    Some(String::from(TOFU_NEEDS_TLS)),
+                );
+                return;
+            }
+            self.set_connection_state(ConnectionState::Connecting, None);
+
             let tls_info = self.tls_info.borrow().as_ref().cloned();
+            let tls_config = tls_info.as_ref().map(|(_, tls_config)| tls_config.clone());
+            // The certificate is checked in the handshake of the connection itself
+            let pin = self
+                .tofu
+                .get()
+                .then(|| ServerPin::new(self.pinned.borrow().clone()));
+            let connector = self.connector.borrow().clone().unwrap_or_else(|| {
+                pin.clone()
+                    .map_or_else(givc_connector, server_pin::connector)
+            });
 
             let (event_tx, event_rx) = async_channel::unbounded();
+            let (pin_tx, pin_rx) = async_channel::bounded(1);
             let (task_tx, task_rx) =
                 async_channel::bounded::<(Task, async_channel::Sender<Response>)>(1);
 
@@ -467,18 +530,35 @@ mod imp {
                     .unwrap()
                     .block_on(async move {
                         let timeout_duration = Duration::from_secs(5);
-                        let admin_client = AdminClient::from_endpoint_address(address, tls_info);
+                        let backend = connector(address, tls_info);
                         let result = {
                             tokio::select! {
                                 () = tokio::time::sleep(timeout_duration) => {
                                     warn!("Watch call timeout");
+                                    let _ = event_tx
+                                        .send(Err(String::from("Connection timed out")))
+                                        .await;
                                     return;
                                 },
-                                result = admin_client.watch() => match result {
-                                    Ok(result) => result,
-                                    Err(e) => {
-                                        error!("Watch call failed: {e}");
-                                        return;
+                                result = backend.watch() => {
+                                    // Told ahead of the outcome, which it may explain
+                                    let check = pin.as_ref().and_then(|pin| pin.take_check());
+                                    if let Some(check) = check {
+                                        let _ = pin_tx.send(check).await;
+                                    }
+                                    drop(pin_tx);
+                                    match result {
+                                        Ok(result) => result,
+                                        Err(e) => {
+                                            error!("Watch call failed: {e:#}");
+                                            let message =
+                                                tls_certificates::describe_connect_error(
+                                                    &e,
+                                                    tls_config.as_ref(),
+                                                );
+                                            let _ = event_tx.send(Err(message)).await;
+                                            return;
+                                        }
                                     }
                                 },
                                 () = async {
@@ -490,9 +570,9 @@ mod imp {
                         };
                         debug!("Connected!");
 
-                        let _ = event_tx.send((result.channel, result.initial)).await;
+                        let _ = event_tx.send(Ok((result.events, result.initial))).await;
                         while let Ok((task, resp)) = task_rx.recv().await {
-                            let res = task(&admin_client).await;
+                            let res = task(&*backend).await;
                             let _ = resp.send(res).await;
                         }
                     });
@@ -502,52 +582,134 @@ mod imp {
                 #[strong(rename_to = model)]
                 self.obj(),
                 async move {
-                    if let Ok((channel, initial)) = event_rx.recv().await {
-                        let this = model.imp();
-                        let n = this.services.borrow().len();
-                        if n > 0 {
-                            this.services.borrow_mut().clear();
-                            model.items_changed(0, n as u32, 0);
+                    let this = model.imp();
+                    let current = || this.generation.get() == generation;
+                    match pin_rx.recv().await {
+                        Ok(PinCheck::Recorded(presented)) if current() => {
+                            info!(
+                                "Recorded admin service certificate {}",
+                                presented.fingerprint
+                            );
+                            *this.pinned.borrow_mut() = Some(presented.clone());
+                            model.emit_by_name::<()>("server-certificate-recorded", &[&presented]);
                         }
-                        this.extend(initial);
-
-                        while let Ok(event) = channel.recv().await {
-                            match event {
-                                Event::UnitStatusChanged(result) => {
-                                    debug!("Status: {result:?}");
-                                    if let Some((_, obj)) =
-                                        this.find(|obj| obj.name() == result.name)
-                                    {
-                                        obj.update(result);
-                                    }
-                                }
-                                Event::UnitShutdown(result) => {
-                                    debug!("Shutdown info: {result:?}");
-                                    //Remove service/app, update VM
-                                    #[allow(clippy::cast_possible_truncation)]
-                                    if let Some((pos, obj)) =
-                                        this.find(|obj| obj.name() == result.name)
-                                    {
-                                        if obj.is_vm() {
-                                            obj.update(result);
-                                        } else {
-                                            this.services.borrow_mut().remove(pos);
-                                            model.items_changed(pos as u32, 1, 0);
-                                        }
-                                    }
-                                }
-                                Event::UnitRegistered(result) => {
-                                    debug!("Unit registered {result:?}");
-                                    this.extend(Some(result));
-                                }
+                        Ok(PinCheck::Changed(presented)) => {
+                            if current() {
+                                warn!(
+                                    "Admin service certificate changed to {}",
+                                    presented.fingerprint
+                                );
+                                this.set_connection_state(
+                                    ConnectionState::Failed,
+                                    Some(String::from("The admin service certificate changed")),
+                                );
+                                // Not retried, only the user can decide to trust it
+                                let pinned = this.pinned.borrow().clone().unwrap_or_default();
+                                model.emit_by_name::<()>(
+                                    "server-certificate-changed",
+                                    &[&pinned, &presented],
+                                );
                             }
+                            return;
                         }
+                        Ok(PinCheck::Recorded(_)) | Err(_) => {}
+                    }
+
+                    let (mut events, initial) = match event_rx.recv().await {
+                        Ok(Ok(connection)) => connection,
+                        Ok(Err(e)) => {
+                            if this.generation.get() == generation {
+                                this.set_connection_state(ConnectionState::Failed, Some(e));
+                                this.schedule_retry();
+                            }
+                            return;
+                        }
+                        Err(_) => return,
+                    };
+
+                    this.retry_attempt.set(0);
+                    this.set_connection_state(ConnectionState::Connected, None);
+                    let n = this.services.borrow().len();
+                    if n > 0 {
+                        this.services.borrow_mut().clear();
+                        model.items_changed(0, n as u32, 0);
+                    }
+                    this.extend(initial);
+
+                    while let Some(event) = events.next().await {
+                        this.handle_event(event);
+                    }
+
+                    if this.generation.get() == generation {
+                        warn!("Watch stream ended");
+                        this.set_connection_state(
+                            ConnectionState::Failed,
+                            Some(String::from("Connection to admin service lost")),
+                        );
+                        this.schedule_retry();
                     }
                 }
             )));
         }
+
+        fn update(&self, obj: &ServiceGObject, result: QueryResult) {
+            let (status, trust_level, operation) =
+                (obj.status(), obj.trust_level(), obj.operation());
+            obj.update(result);
+            let model = self.obj();
+            model.emit_by_name::<()>("service-changed", &[obj]);
+            model.emit_by_name::<()>(
+                "service-transition",
+                &[obj, &status, &trust_level, &operation],
+            );
+        }
+
+        pub(super) fn handle_event(&self, event: Event) {
+            let model = self.obj();
+            match event {
+                Event::UnitStatusChanged(result) => {
+                    debug!("Status: {result:?}");
+                    if let Some((_, obj)) = self.find(|obj| obj.name() == result.name) {
+                        self.update(&obj, result);
+                    }
+                }
+                Event::UnitShutdown(result) => {
+                    debug!("Shutdown info: {result:?}");
+                    //Remove service/app, update VM
+                    #[allow(clippy::cast_possible_truncation)]
+                    if let Some((pos, obj)) = self.find(|obj| obj.name() == result.name) {
+                        if obj.is_vm() {
+                            self.update(&obj, result);
+                        } else {
+                            self.services.borrow_mut().remove(pos);
+                            model.items_changed(pos as u32, 1, 0);
+                        }
+                    }
+                }
+                Event::UnitRegistered(result) => {
+                    debug!("Unit registered {result:?}");
+                    self.extend(Some(result));
+                }
+            }
+        }
     }
+}
 
+// Disconnects the signal handler when dropped
+struct SignalGuard(Object, Option<SignalHandlerId>);
+
+impl SignalGuard {
+    fn new(obj: &impl IsA<Object>, id: SignalHandlerId) -> Self {
+        Self(obj.clone().upcast(), Some(id))
+    }
+}
+
+impl Drop for SignalGuard {
+    fn drop(&mut self) {
+        if let Some(id) = self.1.take() {
+            self.0.disconnect(id);
+        }
+    }
 }
 
 glib::wrapper! {
@@ -561,8 +723,130 @@ impl Default for ServiceModel {
 }
 
 impl ServiceModel {
-    pub fn set_tls_info(&self, name: String, config: TlsConfig) {
-        self.imp().set_tls_info(name, config);
+    /// TLS name and configuration, `None` to connect without TLS.
+    pub fn set_tls_info(&self, tls_info: Option<(String, TlsConfig)>) {
+        self.imp().set_tls_info(tls_info);
+    }
+
+    /// Whether to trust the admin service certificate on first use, and the certificate
+    /// recorded so far.
+    pub fn set_server_pin(&self, tofu: bool, pinned: Option<PinnedCertificate>) {
+        self.imp().set_server_pin(tofu, pinned);
+    }
+
+    /// Talk to the admin service through the backends made by `connector` instead of the
+    /// givc client.
+    pub fn set_connector(&self, connector: Connector) {
+        self.imp().set_connector(connector);
+    }
+
+    /// Name of the admin service as shown to the user: the TLS name if set, the address otherwise.
+    pub fn target_name(&self) -> String {
+        self.imp().target_name()
+    }
+
+    pub fn connect_service_changed<F: Fn(&Self, &ServiceGObject) + 'static>(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_local("service-changed", false, move |values| {
+            let model = values[0].get::<Self>().unwrap();
+            let obj = values[1].get::<ServiceGObject>().unwrap();
+            f(&model, &obj);
+            None
+        })
+    }
+
+    pub fn connect_server_certificate_recorded<F: Fn(&Self, &PinnedCertificate) + 'static>(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_local("server-certificate-recorded", false, move |values| {
+            let model = values[0].get::<Self>().unwrap();
+            let presented = values[1].get::<PinnedCertificate>().unwrap();
+            f(&model, &presented);
+            None
+        })
+    }
+
+    /// Connect to refused connections, with the recorded and the presented certificate.
+    pub fn connect_server_certificate_changed<
+        F: Fn(&Self, &PinnedCertificate, &PinnedCertificate) + 'static,
+    >(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_local("server-certificate-changed", false, move |values| {
+            let model = values[0].get::<Self>().unwrap();
+            let pinned = values[1].get::<PinnedCertificate>().unwrap();
+            let presented = values[2].get::<PinnedCertificate>().unwrap();
+            f(&model, &pinned, &presented);
+            None
+        })
+    }
+
+    /// All known units, the host first, then by VM name, each VM before its units and the
+    /// units by name. This is the order of the model.
+    pub fn services(&self) -> Vec<ServiceGObject> {
+        self.imp().services.borrow().clone()
+    }
+
+    pub fn service(&self, name: &str) -> Option<ServiceGObject> {
+        self.imp()
+            .find(|obj| obj.name() == name)
+            .map(|(_, obj)| obj)
+    }
+
+    /// Connect to status updates, with the status and trust level the unit had before and the
+    /// operation that was in flight on it.
+    pub fn connect_service_transition<
+        F: Fn(&Self, &ServiceGObject, VMStatus, TrustLevel, ServiceOperation) + 'static,
+    >(
+        &self,
+        f: F,
+    ) -> SignalHandlerId {
+        self.connect_local("service-transition", false, move |values| {
+            let model = values[0].get::<Self>().unwrap();
+            let obj = values[1].get::<ServiceGObject>().unwrap();
+            let status = values[2].get::<VMStatus>().unwrap();
+            let trust_level = values[3].get::<TrustLevel>().unwrap();
+            let operation = values[4].get::<ServiceOperation>().unwrap();
+            f(&model, &obj, status, trust_level, operation);
+            None
+        })
+    }
+
+    /// The unit named `name`, or else the VM named `name`.
+    pub fn find_unit(&self, name: &str) -> Option<ServiceGObject> {
+        self.service(name).or_else(|| {
+            self.imp()
+                .find(|obj| obj.is_vm() && obj.vm_name() == name)
+                .map(|(_, obj)| obj)
+        })
+    }
+
+    pub fn has_vm(&self, vm_name: &str) -> bool {
+        self.imp()
+            .find(|obj| obj.is_vm() && obj.vm_name() == vm_name)
+            .is_some()
+    }
+
+    /// Apps and services running inside the VM named `vm_name`, excluding the VM itself.
+    pub fn units_in_vm(&self, vm_name: &str) -> Vec<ServiceGObject> {
+        self.imp()
+            .services
+            .borrow()
+            .iter()
+            .filter(|obj| !obj.is_vm() && obj.vm_name() == vm_name)
+            .cloned()
+            .collect()
+    }
+
+    /// Drop the current connection, if any, and connect again without waiting for the
+    /// backoff timer.
+    pub fn reconnect_now(&self) {
+        info!("Forced reconnect");
+        self.imp().delayed_reconnect();
     }
 
     pub async fn start_service(&self, obj: ServiceGObject) -> Result<StartResponse, anyhow::Error> {
@@ -596,15 +880,94 @@ impl ServiceModel {
             .await
     }
 
-    #[allow(clippy::unused_async)]
+    /// How long a unit may stay busy with an operation before the window offers actions
+    /// on it again, the restart timeout.
+    pub fn operation_timeout(&self) -> std::time::Duration {
+        std::time::Duration::from_secs(self.restart_timeout().into())
+    }
+
+    /// There is no restart call in the admin service, so restart is done as stop, waiting
+    /// for the unit to power off (or to disappear, for apps and services), and start.
     pub async fn restart_service(
         &self,
-        _obj: &ServiceGObject,
+        obj: &ServiceGObject,
     ) -> Result<StartResponse, anyhow::Error> {
-        warn!("Restart is not implemented on client lib!");
-        //no restart in admin_client
-        //self.admin_client.restart(name);
-        Err(anyhow::anyhow!("not implemented"))
+        let name = obj.name();
+        let timeout = self.restart_timeout();
+
+        // Subscribe before stopping, so a quick shutdown event is not missed
+        let stopped = self.wait_stopped(obj);
+        self.stop_service(obj)
+            .await
+            .with_context(|| format!("Restart of {name} failed: could not stop it"))?;
+        glib::future_with_timeout(std::time::Duration::from_secs(timeout.into()), stopped)
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!("Restart of {name} failed: it did not stop within {timeout} s")
+            })?
+            .with_context(|| format!("Restart of {name} failed"))?;
+        debug!("Restart: {name} stopped, starting it again");
+        obj.begin_operation(ServiceOperation::Starting, self.operation_timeout());
+        self.start_service(obj.clone())
+            .await
+            .with_context(|| format!("Restart of {name} failed: could not start it again"))
+    }
+
+    /// Resolves once `obj` is powered off or gone from the list. Fails if the connection is
+    /// lost or reset meanwhile, as the list then no longer tells whether the unit stopped.
+    fn wait_stopped(
+        &self,
+        obj: &ServiceGObject,
+    ) -> impl std::future::Future<Output = Result<(), anyhow::Error>> + use<> {
+        let (tx, rx) = async_channel::unbounded();
+        let status_handler = SignalGuard::new(
+            obj,
+            obj.connect_status_notify(glib::clone!(
+                #[strong]
+                tx,
+                move |_| {
+                    let _ = tx.try_send(());
+                }
+            )),
+        );
+        let state_handler = SignalGuard::new(
+            self,
+            self.connect_connection_state_notify(glib::clone!(
+                #[strong]
+                tx,
+                move |_| {
+                    let _ = tx.try_send(());
+                }
+            )),
+        );
+        let items_handler = SignalGuard::new(
+            self,
+            self.connect_items_changed(move |_, _, removed, _| {
+                if removed > 0 {
+                    let _ = tx.try_send(());
+                }
+            }),
+        );
+        let model = self.clone();
+        let obj = obj.clone();
+        let generation = self.imp().generation.get();
+
+        async move {
+            let _guards = (status_handler, state_handler, items_handler);
+            loop {
+                if model.imp().generation.get() != generation
+                    || model.connection_state() != ConnectionState::Connected
+                {
+                    anyhow::bail!("the connection to the admin service was lost meanwhile");
+                }
+                if matches!(obj.status(), VMStatus::PoweredOff)
+                    || model.imp().find(|o| o == &obj).is_none()
+                {
+                    return Ok(());
+                }
+                rx.recv().await?;
+            }
+        }
     }
 
     pub async fn stop_service(&self, obj: &ServiceGObject) -> Result<(), anyhow::Error> {
@@ -640,54 +1003,16 @@ impl ServiceModel {
             .await
     }
 
-    #[cfg(not(feature = "mock"))]
     pub async fn get_stats(&self, vm: String) -> Result<StatsResponse, anyhow::Error> {
         self.imp()
             .client_cmd(async |client| client.get_stats(vm).await)
             .await
     }
 
-    #[cfg(not(feature = "mock"))]
     pub async fn get_sysinfo_status_from_host(&self) -> Result<HostSysinfoStatus, anyhow::Error> {
         self.imp().get_sysinfo_status_from_host().await
     }
 
-    #[cfg(feature = "mock")]
-    #[allow(clippy::unused_self)]
-    pub fn get_stats(
-        &self,
-        _vm: String,
-    ) -> impl std::future::Future<Output = Result<StatsResponse, anyhow::Error>> {
-        use givc_common::pb::stats::{MemoryStats, ProcessStats};
-        async {
-            Ok(StatsResponse {
-                memory: Some(MemoryStats {
-                    total: 200_000_000,
-                    available: 100_000_000,
-                    free: 50_000_000,
-                    ..Default::default()
-                }),
-                process: Some(ProcessStats {
-                    user_cycles: 100_000,
-                    sys_cycles: 50_000,
-                    total_cycles: 200_000,
-                    ..Default::default()
-                }),
-                ..Default::default()
-            })
-        }
-    }
-
-    #[cfg(feature = "mock")]
-    #[allow(clippy::unused_async, clippy::unused_self)]
-    pub async fn get_sysinfo_status_from_host(&self) -> Result<HostSysinfoStatus, anyhow::Error> {
-        Ok(HostSysinfoStatus {
-            ghaf_version: "0.0.0-mock".to_string(),
-            secure_boot: Some(false),
-            disk_encryption: Some(false),
-        })
-    }
-
     #[allow(clippy::unused_async, clippy::unused_self)]
     pub async fn check_for_update(&self) -> Result<(), anyhow::Error> {
         warn!("Check for update request");
@@ -699,3 +1024,195 @@ impl ServiceModel {
         warn!("Update request");
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use givc_common::pb::admin::QueryListItem;
+    use givc_common::query::{Event, QueryResult, TrustLevel, VMStatus};
+
+    use super::*;
+
+    const VM: &str = "microvm@chrome-vm.service";
+    const APP: &str = "chrome@1.service";
+
+    fn unit(name: &str, service_type: &str, status: &str, trust_level: &str) -> QueryResult {
+        QueryResult::try_from(QueryListItem {
+            name: name.to_owned(),
+            description: String::new(),
+            vm_status: status.to_owned(),
+            trust_level: trust_level.to_owned(),
+            vm_type: String::from("AppVM"),
+            service_type: service_type.to_owned(),
+            vm_name: Some(String::from("chrome-vm")),
+            agent_name: None,
+        })
+        .unwrap()
+    }
+
+    #[derive(Default)]
+    struct Emitted {
+        changed: Vec<String>,
+        // Unit name, status and trust level before the update, operation in flight
+        transitions: Vec<(String, VMStatus, TrustLevel, ServiceOperation)>,
+        // Position, removed and added items
+        items_changed: Vec<(u32, u32, u32)>,
+    }
+
+    /// A model listing chrome-vm and an app running in it, and what it emits from then on.
+    fn model() -> (ServiceModel, Rc<RefCell<Emitted>>) {
+        let model = ServiceModel::default();
+        model.imp().extend([
+            unit(VM, "VM", "Running", "Secure"),
+            unit(APP, "App", "Running", "Secure"),
+        ]);
+
+        let emitted = Rc::new(RefCell::new(Emitted::default()));
+        model.connect_service_changed(glib::clone!(
+            #[strong]
+            emitted,
+            move |_, obj| emitted.borrow_mut().changed.push(obj.name())
+        ));
+        model.connect_service_transition(glib::clone!(
+            #[strong]
+            emitted,
+            move |_, obj, status, trust_level, operation| {
+                emitted
+                    .borrow_mut()
+                    .transitions
+                    .push((obj.name(), status, trust_level, operation));
+            }
+        ));
+        model.connect_items_changed(glib::clone!(
+            #[strong]
+            emitted,
+            move |_, pos, removed, added| {
+                emitted
+                    .borrow_mut()
+                    .items_changed
+                    .push((pos, removed, added));
+            }
+        ));
+        (model, emitted)
+    }
+
+    fn names(model: &ServiceModel) -> Vec<String> {
+        model.services().iter().map(ServiceGObject::name).collect()
+    }
+
+    #[test]
+    fn registered_units_are_listed_in_order() {
+        let model = ServiceModel::default();
+        let cases = [
+            // The host comes with the first units
+            (unit(VM, "VM", "Running", "Secure"), vec!["ghaf-host", VM]),
+            (
+                unit(APP, "App", "Running", "Secure"),
+                vec!["ghaf-host", VM, APP],
+            ),
+            (
+                unit("chrome@2.service", "App", "Running", "Secure"),
+                vec!["ghaf-host", VM, APP, "chrome@2.service"],
+            ),
+            // Listed already
+            (
+                unit(APP, "App", "Paused", "Secure"),
+                vec!["ghaf-host", VM, APP, "chrome@2.service"],
+            ),
+        ];
+        for (result, expected) in cases {
+            model.imp().handle_event(Event::UnitRegistered(result));
+            assert_eq!(names(&model), expected);
+            assert_eq!(model.n_items() as usize, expected.len());
+        }
+    }
+
+    #[test]
+    fn status_changes_update_units_in_place() {
+        let (model, emitted) = model();
+        let vm = model.service(VM).unwrap();
+        let before = names(&model);
+
+        model.imp().handle_event(Event::UnitStatusChanged(unit(
+            VM, "VM", "Paused", "Warning",
+        )));
+        assert_eq!(model.service(VM).as_ref(), Some(&vm));
+        assert!(matches!(vm.status(), VMStatus::Paused));
+        assert!(matches!(vm.trust_level(), TrustLevel::Warning));
+
+        model.imp().handle_event(Event::UnitStatusChanged(unit(
+            "unknown.service",
+            "Svc",
+            "Running",
+            "Secure",
+        )));
+
+        let emitted = emitted.borrow();
+        assert_eq!(names(&model), before);
+        assert!(emitted.items_changed.is_empty());
+        assert_eq!(emitted.changed, [VM]);
+        assert!(matches!(
+            emitted.transitions.as_slice(),
+            [(name, VMStatus::Running, TrustLevel::Secure, ServiceOperation::Idle)] if name == VM
+        ));
+    }
+
+    #[test]
+    fn shutdown_updates_vms_and_removes_other_units() {
+        let (model, emitted) = model();
+        let vm = model.service(VM).unwrap();
+        let app_pos = model.services().iter().position(|obj| obj.name() == APP);
+
+        model.imp().handle_event(Event::UnitShutdown(unit(
+            APP,
+            "App",
+            "PoweredOff",
+            "Secure",
+        )));
+        assert_eq!(names(&model), ["ghaf-host", VM]);
+        model
+            .imp()
+            .handle_event(Event::UnitShutdown(unit(VM, "VM", "PoweredOff", "Secure")));
+        assert_eq!(names(&model), ["ghaf-host", VM]);
+        assert!(matches!(vm.status(), VMStatus::PoweredOff));
+
+        let emitted = emitted.borrow();
+        let app_pos = u32::try_from(app_pos.unwrap()).unwrap();
+        assert_eq!(emitted.items_changed, [(app_pos, 1, 0)]);
+        assert_eq!(emitted.changed, [VM]);
+        assert!(matches!(
+            emitted.transitions.as_slice(),
+            [(name, VMStatus::Running, TrustLevel::Secure, ServiceOperation::Idle)] if name == VM
+        ));
+    }
+
+    #[test]
+    fn operations_end_on_the_status_they_wait_for() {
+        let (model, emitted) = model();
+        let vm = model.service(VM).unwrap();
+        vm.set_operation(ServiceOperation::Stopping);
+
+        // A trust level refresh while the VM is still shutting down
+        model.imp().handle_event(Event::UnitStatusChanged(unit(
+            VM, "VM", "Running", "Warning",
+        )));
+        assert_eq!(vm.operation(), ServiceOperation::Stopping);
+        model
+            .imp()
+            .handle_event(Event::UnitShutdown(unit(VM, "VM", "PoweredOff", "Warning")));
+        assert_eq!(vm.operation(), ServiceOperation::Idle);
+
+        let operations: Vec<_> = emitted
+            .borrow()
+            .transitions
+            .iter()
+            .map(|(_, _, _, operation)| *operation)
+            .collect();
+        assert_eq!(
+            operations,
+            [ServiceOperation::Stopping, ServiceOperation::Stopping]
+        );
+    }
+}
@@ -2,6 +2,7 @@ use gtk::glib;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 
+use crate::control_action::ServiceOperation;
 use crate::service_gobject::ServiceGObject;
 
 mod imp {
@@ -26,6 +27,8 @@ mod imp {
         pub vm_icon: TemplateChild<gtk::Image>,
         #[template_child]
         pub security_icon: TemplateChild<SecurityIcon>,
+        #[template_child]
+        pub operation_spinner: TemplateChild<adw::Spinner>,
 
         // Vector holding the bindings to properties of `TaskObject`
         pub bindings: RefCell<Vec<Binding>>,
@@ -73,31 +76,12 @@ impl ServiceRow {
         let title = self.imp().title_label.get();
         let subtitle = self.imp().subtitle_label.get();
         let security_icon = self.imp().security_icon.get();
+        let operation_spinner = self.imp().operation_spinner.get();
         let mut bindings = self.imp().bindings.borrow_mut();
         let is_vm = object.is_vm();
 
         let name_property = if is_vm { "display-name" } else { "name" };
 
-        let margin_binding = object
-            .bind_property("is-vm", self, "margin-start")
-            .transform_to(move |binding, is_vm: bool| {
-                Some(
-                    if is_vm { 0i32 } else { 16 }
-                        + if binding
-                            .source()
-                            .and_downcast::<ServiceGObject>()
-                            .is_some_and(|obj| &obj.vm_name() == "ghaf-host")
-                        {
-                            0
-                        } else {
-                            16
-                        },
-                )
-            })
-            .sync_create()
-            .build();
-        bindings.push(margin_binding);
-
         let title_binding = object
             .bind_property(name_property, &title, "label")
             //.bidirectional()
@@ -120,6 +104,16 @@ impl ServiceRow {
         // Save binding
         bindings.push(security_binding);
 
+        let operation_binding = object
+            .bind_property("operation", &operation_spinner, "visible")
+            .sync_create()
+            .transform_to(|_, operation: ServiceOperation| {
+                Some(operation != ServiceOperation::Idle)
+            })
+            .build();
+        // Save binding
+        bindings.push(operation_binding);
+
         //block was left here as example
         /*/ Bind `task_object.completed` to `task_row.content_label.attributes`
         let content_label_binding = task_object
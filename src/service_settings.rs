@@ -1,8 +1,9 @@
-use givc_common::types::VmType;
+use givc_common::query::VMStatus;
 use gtk::prelude::*;
 use gtk::subclass::prelude::*;
 use gtk::{gio, glib};
 
+use crate::control_action::ServiceOperation;
 use crate::service_gobject::ServiceGObject;
 use crate::window::ControlPanelGuiWindow;
 
@@ -75,9 +76,31 @@ mod imp {
         #[template_child]
         pub action_menu_button: TemplateChild<MenuButton>,
         #[template_child]
+        pub operation_spinner: TemplateChild<adw::Spinner>,
+        #[template_child]
         pub popover_menu: TemplateChild<Popover>,
         #[template_child]
         pub popover_menu_2: TemplateChild<Popover>,
+        #[template_child]
+        pub start_button: TemplateChild<Button>,
+        #[template_child]
+        pub pause_button: TemplateChild<Button>,
+        #[template_child]
+        pub resume_button: TemplateChild<Button>,
+        #[template_child]
+        pub restart_button: TemplateChild<Button>,
+        #[template_child]
+        pub shutdown_button: TemplateChild<Button>,
+        #[template_child]
+        pub start_button_2: TemplateChild<Button>,
+        #[template_child]
+        pub pause_button_2: TemplateChild<Button>,
+        #[template_child]
+        pub resume_button_2: TemplateChild<Button>,
+        #[template_child]
+        pub restart_button_2: TemplateChild<Button>,
+        #[template_child]
+        pub stop_button: TemplateChild<Button>,
 
         #[template_child]
         pub cpu_sys_serie: TemplateChild<Serie>,
@@ -153,6 +176,18 @@ mod imp {
             self.popover_menu_2.popdown();
         }
 
+        #[template_callback]
+        fn on_resume_clicked(&self) {
+            self.emit_control_action(ControlAction::Resume);
+            self.popover_menu_2.popdown();
+        }
+
+        #[template_callback]
+        fn on_restart_clicked(&self) {
+            self.emit_control_action(ControlAction::Restart);
+            self.popover_menu_2.popdown();
+        }
+
         #[template_callback]
         fn on_pause_clicked(&self) {
             self.emit_control_action(ControlAction::Pause);
@@ -160,6 +195,28 @@ mod imp {
         }
     } //end #[gtk::template_callbacks]
 
+    impl ServiceSettings {
+        pub(super) fn action_buttons(&self, is_vm: bool) -> [(ControlAction, Button); 5] {
+            if is_vm {
+                [
+                    (ControlAction::Start, self.start_button.get()),
+                    (ControlAction::Pause, self.pause_button.get()),
+                    (ControlAction::Resume, self.resume_button.get()),
+                    (ControlAction::Restart, self.restart_button.get()),
+                    (ControlAction::Shutdown, self.shutdown_button.get()),
+                ]
+            } else {
+                [
+                    (ControlAction::Start, self.start_button_2.get()),
+                    (ControlAction::Pause, self.pause_button_2.get()),
+                    (ControlAction::Resume, self.resume_button_2.get()),
+                    (ControlAction::Restart, self.restart_button_2.get()),
+                    (ControlAction::Shutdown, self.stop_button.get()),
+                ]
+            }
+        }
+    }
+
     impl ObjectImpl for ServiceSettings {
         fn constructed(&self) {
             // Call "constructed" on parent
@@ -311,10 +368,35 @@ impl ServiceSettings {
                 .set_popover(Some(&self.imp().popover_menu_2.get()));
         }
 
-        self.imp().action_menu_button.set_sensitive(!matches!(
-            object.vm_type(),
-            VmType::AdmVM | VmType::SysVM | VmType::Host
-        ));
+        //offer only the actions that make sense in the current state
+        for (action, button) in self.imp().action_buttons(object.is_vm()) {
+            let action_binding = object
+                .bind_property("status", &button, "visible")
+                .sync_create()
+                .transform_to(move |_, status: VMStatus| Some(action.is_available(status)))
+                .build();
+            bindings.push(action_binding);
+        }
+
+        //no actions on system VMs, nor while another action is in flight
+        let controllable = object.is_controllable();
+        let action_menu_binding = object
+            .bind_property("operation", &*self.imp().action_menu_button, "sensitive")
+            .sync_create()
+            .transform_to(move |_, operation: ServiceOperation| {
+                Some(controllable && operation == ServiceOperation::Idle)
+            })
+            .build();
+        bindings.push(action_menu_binding);
+
+        let operation_binding = object
+            .bind_property("operation", &*self.imp().operation_spinner, "visible")
+            .sync_create()
+            .transform_to(|_, operation: ServiceOperation| {
+                Some(operation != ServiceOperation::Idle)
+            })
+            .build();
+        bindings.push(operation_binding);
         *self.imp().service.borrow_mut() = Some(object.clone());
 
         if is_vm_or_app {
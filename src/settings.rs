@@ -15,6 +15,7 @@ mod imp {
     //use crate::service_gobject::ServiceGObject; will be used in the future
     use crate::about::AboutPage;
     use crate::control_action::ControlAction;
+    use crate::history_page::HistoryPage;
     use crate::language_region_settings_page::LanguageRegionSettingsPage;
     use crate::service_gobject::ServiceGObject;
     use crate::settings_action::SettingsAction;
@@ -29,6 +30,8 @@ mod imp {
         #[template_child]
         pub language_region_settings_page: TemplateChild<LanguageRegionSettingsPage>,
         #[template_child]
+        pub history_page: TemplateChild<HistoryPage>,
+        #[template_child]
         pub about_page: TemplateChild<AboutPage>,
         //pub vm_model: RefCell<ListModel>,
 
@@ -148,6 +151,19 @@ impl Settings {
             .set_timezone_model(model, selected);
     }
 
+    /// Show the page named `name`: `locale`, `history` or `about`. Returns false if there
+    /// is no such page.
+    pub fn show_page(&self, name: &str) -> bool {
+        let list_box = &self.imp().list_box;
+        let row = (0..)
+            .map_while(|idx| list_box.row_at_index(idx))
+            .find(|row| row.widget_name() == name);
+        if let Some(row) = &row {
+            list_box.select_row(Some(row));
+        }
+        row.is_some()
+    }
+
     pub fn init(&self) {
         if let Some(row) = self.imp().list_box.row_at_index(0) {
             self.imp().list_box.select_row(Some(&row));
@@ -0,0 +1,95 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+use givc_common::query::{TrustLevel, VMStatus};
+use givc_common::types::VmType;
+
+use crate::service_gobject::ServiceGObject;
+
+/// Order of the services list, applied to VMs and to the units within each VM.
+#[derive(
+    Debug,
+    Default,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Serialize,
+    Deserialize,
+    strum::EnumString,
+    strum::IntoStaticStr,
+)]
+#[serde(rename_all = "kebab-case")]
+#[strum(serialize_all = "kebab-case")]
+pub enum SortOrder {
+    /// Host first, then alphabetical by VM
+    #[default]
+    Vm,
+    /// Running first, then paused, then powered off
+    Status,
+    /// Least secure first
+    TrustLevel,
+    /// Admin VM, system VMs, then app VMs
+    VmType,
+    /// Alphabetical by display name
+    Name,
+}
+
+impl SortOrder {
+    /// Whether the order depends on what updates from the admin service change, the status
+    /// and the trust level of units.
+    pub fn follows_updates(self) -> bool {
+        matches!(self, Self::Status | Self::TrustLevel)
+    }
+
+    pub fn compare(self, a: &ServiceGObject, b: &ServiceGObject) -> Ordering {
+        let by_key = || a.sort_key().cmp(&b.sort_key());
+        match self {
+            Self::Vm => by_key(),
+            Self::Status => status_rank(a.status())
+                .cmp(&status_rank(b.status()))
+                .then_with(by_key),
+            Self::TrustLevel => trust_rank(a.trust_level())
+                .cmp(&trust_rank(b.trust_level()))
+                .then_with(by_key),
+            Self::VmType => type_rank(a.vm_type())
+                .cmp(&type_rank(b.vm_type()))
+                .then_with(by_key),
+            Self::Name => a
+                .title()
+                .to_lowercase()
+                .cmp(&b.title().to_lowercase())
+                .then_with(by_key),
+        }
+    }
+}
+
+fn status_rank(status: VMStatus) -> u8 {
+    if matches!(status, VMStatus::Running) {
+        0
+    } else if matches!(status, VMStatus::Paused) {
+        1
+    } else {
+        2
+    }
+}
+
+fn trust_rank(trust_level: TrustLevel) -> u8 {
+    match trust_level {
+        TrustLevel::NotSecure => 0,
+        TrustLevel::Warning => 1,
+        TrustLevel::Secure => 2,
+    }
+}
+
+fn type_rank(vm_type: VmType) -> u8 {
+    if matches!(vm_type, VmType::Host) {
+        0
+    } else if matches!(vm_type, VmType::AdmVM) {
+        1
+    } else if matches!(vm_type, VmType::SysVM) {
+        2
+    } else {
+        3
+    }
+}
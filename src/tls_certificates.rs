@@ -0,0 +1,221 @@
+//! Reading the PEM files of a connection profile, to show what they hold and to refuse a
+//! set that cannot work before connecting with it.
+
+use anyhow::{Context, bail};
+use chrono::{DateTime, Utc};
+use std::fmt;
+use std::path::Path;
+use x509_parser::extensions::GeneralName;
+use x509_parser::pem::Pem;
+
+use givc_client::endpoint::TlsConfig;
+
+use crate::connection_profiles::ConnectionProfile;
+
+pub const TIME_FORMAT: &str = "%Y-%m-%d %H:%M UTC";
+
+/// The parts of a certificate worth showing to the user.
+#[derive(Debug, Clone)]
+pub struct CertificateInfo {
+    pub subject: String,
+    pub issuer: String,
+    /// Subject alternative names
+    pub sans: Vec<String>,
+    pub not_before: DateTime<Utc>,
+    pub not_after: DateTime<Utc>,
+}
+
+fn general_name(name: &GeneralName) -> String {
+    match name {
+        GeneralName::DNSName(dns) => format!("DNS:{dns}"),
+        GeneralName::IPAddress(bytes) => match <[u8; 4]>::try_from(*bytes) {
+            Ok(v4) => format!("IP:{}", std::net::Ipv4Addr::from(v4)),
+            Err(_) => <[u8; 16]>::try_from(*bytes).map_or_else(
+                |_| format!("IP:{bytes:02x?}"),
+                |v6| format!("IP:{}", std::net::Ipv6Addr::from(v6)),
+            ),
+        },
+        other => other.to_string(),
+    }
+}
+
+fn pem_blocks(path: &Path) -> Result<Vec<Pem>, anyhow::Error> {
+    let data = std::fs::read(path).with_context(|| format!("Cannot read {}", path.display()))?;
+    Pem::iter_from_buffer(&data)
+        .collect::<Result<Vec<_>, _>>()
+        .with_context(|| format!("{} is not a PEM file", path.display()))
+}
+
+impl CertificateInfo {
+    /// The first certificate in the PEM file at `path`.
+    pub fn load(path: &Path) -> Result<Self, anyhow::Error> {
+        Self::load_chain(path)?
+            .into_iter()
+            .next()
+            .with_context(|| format!("No certificate in {}", path.display()))
+    }
+
+    /// All certificates in the PEM file at `path`, in file order.
+    pub fn load_chain(path: &Path) -> Result<Vec<Self>, anyhow::Error> {
+        pem_blocks(path)?
+            .iter()
+            .filter(|pem| pem.label == "CERTIFICATE")
+            .map(|pem| Self::parse(pem, path))
+            .collect()
+    }
+
+    fn parse(pem: &Pem, path: &Path) -> Result<Self, anyhow::Error> {
+        Self::from_der(&pem.contents)
+            .with_context(|| format!("Malformed certificate in {}", path.display()))
+    }
+
+    /// The certificate in DER encoding `der`.
+    pub fn from_der(der: &[u8]) -> Result<Self, anyhow::Error> {
+        let (_, cert) = x509_parser::parse_x509_certificate(der)?;
+
+        let sans = match cert.subject_alternative_name() {
+            Ok(Some(san)) => san.value.general_names.iter().map(general_name).collect(),
+            Ok(None) => Vec::new(),
+            Err(e) => bail!("Malformed subject alternative names: {e}"),
+        };
+        let validity = cert.validity();
+        let time = |asn1: x509_parser::time::ASN1Time| {
+            DateTime::from_timestamp(asn1.timestamp(), 0).unwrap_or_default()
+        };
+        Ok(Self {
+            subject: cert.subject().to_string(),
+            issuer: cert.issuer().to_string(),
+            sans,
+            not_before: time(validity.not_before),
+            not_after: time(validity.not_after),
+        })
+    }
+
+    /// What is wrong with the validity window now, if anything.
+    pub fn validity_problem(&self) -> Option<String> {
+        let now = Utc::now();
+        if now < self.not_before {
+            Some(format!(
+                "not valid before {}",
+                self.not_before.format(TIME_FORMAT)
+            ))
+        } else if now > self.not_after {
+            Some(format!("expired {}", self.not_after.format(TIME_FORMAT)))
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for CertificateInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Subject: {}", self.subject)?;
+        writeln!(f, "Issuer: {}", self.issuer)?;
+        if !self.sans.is_empty() {
+            writeln!(f, "SANs: {}", self.sans.join(", "))?;
+        }
+        write!(
+            f,
+            "Valid: {} to {}",
+            self.not_before.format(TIME_FORMAT),
+            self.not_after.format(TIME_FORMAT)
+        )
+    }
+}
+
+/// Check that the file at `path` holds a private key.
+pub fn check_key(path: &Path) -> Result<(), anyhow::Error> {
+    if pem_blocks(path)?
+        .iter()
+        .any(|pem| pem.label.ends_with("PRIVATE KEY"))
+    {
+        Ok(())
+    } else {
+        bail!("No private key in {}", path.display())
+    }
+}
+
+/// Check that the TLS files of `profile` are readable and its certificates are valid now.
+pub fn check_profile(profile: &ConnectionProfile) -> Result<(), anyhow::Error> {
+    if profile.no_tls {
+        return Ok(());
+    }
+    if profile.tls_name.is_empty() {
+        bail!("Enter the TLS name of the admin service");
+    }
+    // A trust-on-first-use profile offers a client certificate only if there is one
+    if profile.tofu && !profile.cert.exists() {
+        return Ok(());
+    }
+    let certificates = [
+        ("CA certificate", &profile.ca_cert),
+        ("Certificate", &profile.cert),
+    ];
+    // The pin stands in for the CA
+    for (what, path) in certificates.into_iter().skip(usize::from(profile.tofu)) {
+        let info = CertificateInfo::load(path)?;
+        if let Some(problem) = info.validity_problem() {
+            bail!("{what} {}: {problem}", path.display());
+        }
+    }
+    check_key(&profile.key)
+}
+
+/// The TLS error in the chain of `error`. The TLS library reports it wrapped in an I/O
+/// error, which does not show it as its source.
+fn tls_error(error: &anyhow::Error) -> Option<&rustls::Error> {
+    error.chain().find_map(|cause| {
+        cause.downcast_ref::<rustls::Error>().or_else(|| {
+            cause
+                .downcast_ref::<std::io::Error>()
+                .and_then(std::io::Error::get_ref)
+                .and_then(|inner| inner.downcast_ref::<rustls::Error>())
+        })
+    })
+}
+
+/// The first expired certificate among the CA and host certificates of `tls_config`.
+fn expired_certificate(tls_config: &TlsConfig) -> Option<String> {
+    [
+        ("CA certificate", &tls_config.ca_cert_file_path),
+        ("Host certificate", &tls_config.cert_file_path),
+    ]
+    .into_iter()
+    .find_map(|(what, path)| {
+        let cert = CertificateInfo::load_chain(path)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|cert| cert.not_after < Utc::now())?;
+        Some(format!(
+            "{what} {} expired on {}",
+            path.display(),
+            cert.not_after.format(TIME_FORMAT)
+        ))
+    })
+}
+
+/// What to tell the user when connecting with `tls_config` failed with `error`: which
+/// certificate expired if that is why the TLS handshake failed, the error itself otherwise.
+pub fn describe_connect_error(error: &anyhow::Error, tls_config: Option<&TlsConfig>) -> String {
+    let Some(tls_config) = tls_config else {
+        return error.to_string();
+    };
+    let tls_error = tls_error(error);
+    let handshake_failed =
+        tls_error.is_some() || format!("{error:#}").to_lowercase().contains("handshake");
+    let server_expired = matches!(
+        tls_error,
+        Some(rustls::Error::InvalidCertificate(
+            rustls::CertificateError::Expired | rustls::CertificateError::ExpiredContext { .. }
+        ))
+    );
+    if server_expired {
+        return String::from("TLS handshake failed: the admin service certificate expired");
+    }
+    match expired_certificate(tls_config) {
+        Some(expired) if handshake_failed => format!("TLS handshake failed: {expired}"),
+        // Not why this attempt failed, but the next one that gets that far will
+        Some(expired) => format!("{error} ({expired})"),
+        None => error.to_string(),
+    }
+}
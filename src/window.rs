@@ -5,34 +5,57 @@ use gtk::{gio, glib};
 
 use crate::application::ControlPanelGuiApplication;
 pub use crate::application::StatsResponse;
+use crate::audit_log::Origin;
+use crate::connection_profiles::PinnedCertificate;
+use crate::control_action::{ControlAction, ServiceOperation};
+use crate::launch_args::Page;
 use crate::prelude::*;
+use crate::service_gobject::ServiceGObject;
+use givc_common::types::VmType;
 
 mod imp {
     use adw::subclass::prelude::*;
     use gio::ListModel;
     use gtk::prelude::*;
     use gtk::{
-        CompositeTemplate, Image, ListView, MenuButton, SingleSelection, Stack, ToggleButton, gio,
-        glib,
+        ActionBar, Button, CompositeTemplate, CustomFilter, CustomSorter, EveryFilter,
+        FilterChange, FilterListModel, Image, Label, ListView, MenuButton, MultiSelection,
+        Revealer, SearchBar, SearchEntry, SingleSelection, SortListModel, SorterChange, Stack,
+        ToggleButton, TreeExpander, TreeListModel, TreeListRow, gio, glib,
     };
+    use std::cell::{Cell, RefCell};
+    use std::collections::BTreeSet;
 
+    use givc_common::query::{TrustLevel, VMStatus};
+
+    use crate::connection_state::ConnectionState;
     use crate::control_action::ControlAction;
     use crate::prelude::*;
     use crate::service_gobject::ServiceGObject;
+    use crate::service_model::ServiceModel;
     use crate::service_row::ServiceRow;
     use crate::service_settings::ServiceSettings;
     use crate::settings::Settings;
     use crate::settings_action::SettingsAction;
+    use crate::sort_order::SortOrder;
 
     #[derive(Debug, Default, CompositeTemplate)]
     #[template(resource = "/ae/tii/ghaf/controlpanelgui/ui/window.ui")]
     pub struct ControlPanelGuiWindow {
         // Template widgets
         #[template_child]
+        pub toast_overlay: TemplateChild<adw::ToastOverlay>,
+        #[template_child]
         pub header_bar: TemplateChild<adw::HeaderBar>,
         #[template_child]
         pub header_menu_button: TemplateChild<MenuButton>,
         #[template_child]
+        pub connection_indicator: TemplateChild<Button>,
+        #[template_child]
+        pub search_button: TemplateChild<ToggleButton>,
+        #[template_child]
+        pub selection_mode_button: TemplateChild<ToggleButton>,
+        #[template_child]
         pub vm_view_button: TemplateChild<ToggleButton>,
         #[template_child]
         pub settings_view_button: TemplateChild<ToggleButton>,
@@ -42,10 +65,43 @@ mod imp {
         #[template_child]
         pub stack: TemplateChild<Stack>,
 
+        #[template_child]
+        pub connection_banner: TemplateChild<Revealer>,
+        #[template_child]
+        pub connection_banner_title: TemplateChild<Label>,
+        #[template_child]
+        pub search_bar: TemplateChild<SearchBar>,
+        #[template_child]
+        pub search_entry: TemplateChild<SearchEntry>,
+        #[template_child]
+        pub vms_chip: TemplateChild<ToggleButton>,
+        #[template_child]
+        pub apps_chip: TemplateChild<ToggleButton>,
+        #[template_child]
+        pub running_chip: TemplateChild<ToggleButton>,
+        #[template_child]
+        pub paused_chip: TemplateChild<ToggleButton>,
+        #[template_child]
+        pub not_secure_chip: TemplateChild<ToggleButton>,
         #[template_child]
         pub services_list_view: TemplateChild<ListView>,
         #[template_child]
         pub service_settings_box: TemplateChild<ServiceSettings>,
+        #[template_child]
+        pub batch_action_bar: TemplateChild<ActionBar>,
+        #[template_child]
+        pub batch_status_label: TemplateChild<Label>,
+        #[template_child]
+        pub batch_buttons: TemplateChild<gtk::Box>,
+
+        pub service_filter: RefCell<Option<CustomFilter>>,
+        pub root_filter: RefCell<Option<CustomFilter>>,
+        pub service_sorter: RefCell<Option<CustomSorter>>,
+        pub sort_order: Cell<SortOrder>,
+        pub collapsed_vms: RefCell<BTreeSet<String>>,
+        pub single_selection: RefCell<Option<SingleSelection>>,
+        pub multi_selection: RefCell<Option<MultiSelection>>,
+        pub batch_running: Cell<bool>,
 
         #[template_child]
         pub settings_box: TemplateChild<Settings>,
@@ -102,8 +158,56 @@ mod imp {
 
         #[template_callback]
         fn on_control_action(&self, action: ControlAction, object: ServiceGObject) {
-            let app = self.obj().get_app_ref();
-            app.control_service(action, object);
+            self.obj().request_control_action(action, object);
+        }
+
+        #[template_callback]
+        fn on_filter_changed(&self) {
+            if let Some(filter) = self.service_filter.borrow().as_ref() {
+                filter.changed(FilterChange::Different);
+            }
+            if let Some(filter) = self.root_filter.borrow().as_ref() {
+                filter.changed(FilterChange::Different);
+            }
+        }
+
+        #[template_callback]
+        fn on_selection_mode_toggled(&self) {
+            let active = self.selection_mode_button.is_active();
+            let single = self.single_selection.borrow().clone();
+            let multi = self.multi_selection.borrow().clone();
+            let (Some(single), Some(multi)) = (single, multi) else {
+                return;
+            };
+
+            if active {
+                // Start from the unit currently shown in the details pane
+                multi.unselect_all();
+                let selected = single.selected();
+                if selected != gtk::INVALID_LIST_POSITION {
+                    multi.select_item(selected, true);
+                }
+                self.services_list_view.set_model(Some(&multi));
+            } else {
+                self.services_list_view.set_model(Some(&single));
+            }
+            self.batch_action_bar.set_revealed(active);
+            self.update_batch_status();
+        }
+
+        #[template_callback]
+        fn on_batch_pause_clicked(&self) {
+            self.obj().request_batch_action(ControlAction::Pause);
+        }
+
+        #[template_callback]
+        fn on_batch_resume_clicked(&self) {
+            self.obj().request_batch_action(ControlAction::Resume);
+        }
+
+        #[template_callback]
+        fn on_batch_shutdown_clicked(&self) {
+            self.obj().request_batch_action(ControlAction::Shutdown);
         }
 
         #[template_callback]
@@ -112,16 +216,255 @@ mod imp {
             app.perform_setting_action(action);
         }
 
+        /// Whether `obj` passes the search text and every active filter chip. Chips of the
+        /// same kind (unit type, status) widen the match, different kinds narrow it.
+        fn matches_filter(&self, obj: &ServiceGObject) -> bool {
+            if !self.search_bar.is_search_mode() {
+                return true;
+            }
+
+            let text = self.search_entry.text().to_lowercase();
+            if !text.is_empty()
+                && ![obj.name(), obj.display_name(), obj.details()]
+                    .iter()
+                    .any(|field| field.to_lowercase().contains(&text))
+            {
+                return false;
+            }
+
+            let (vms, apps) = (self.vms_chip.is_active(), self.apps_chip.is_active());
+            if (vms || apps) && !(vms && obj.is_vm() || apps && obj.is_app()) {
+                return false;
+            }
+
+            let (running, paused) = (self.running_chip.is_active(), self.paused_chip.is_active());
+            let status = obj.status();
+            if (running || paused)
+                && !(running && matches!(status, VMStatus::Running)
+                    || paused && matches!(status, VMStatus::Paused))
+            {
+                return false;
+            }
+
+            !self.not_secure_chip.is_active() || matches!(obj.trust_level(), TrustLevel::NotSecure)
+        }
+
+        /// Hook up the search entry and refilter whenever a unit changes state.
+        pub fn setup_search(&self, model: &ServiceModel) {
+            model.connect_service_changed(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |_, _| {
+                    if window.imp().search_bar.is_search_mode() {
+                        window.imp().on_filter_changed();
+                    }
+                }
+            ));
+
+            self.search_bar.connect_entry(&*self.search_entry);
+            self.search_bar.set_key_capture_widget(Some(&*self.obj()));
+        }
+
+        /// Build the VM tree over `model`: VMs at the top level with their apps and services
+        /// as children, filtered by the search bar.
+        pub fn setup_service_tree(&self, model: &ServiceModel) -> TreeListModel {
+            let search_filter = CustomFilter::typed(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                #[upgrade_or]
+                true,
+                move |obj: &ServiceGObject| window.imp().matches_filter(obj)
+            ));
+            // Units whose VM is not known go to the top level too. A VM stays visible while
+            // any of its units matches the search.
+            let root_filter = CustomFilter::typed(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                #[strong]
+                model,
+                #[upgrade_or]
+                true,
+                move |obj: &ServiceGObject| {
+                    let imp = window.imp();
+                    if obj.is_vm() {
+                        imp.matches_filter(obj)
+                            || model
+                                .units_in_vm(&obj.vm_name())
+                                .iter()
+                                .any(|unit| imp.matches_filter(unit))
+                    } else {
+                        !model.has_vm(&obj.vm_name()) && imp.matches_filter(obj)
+                    }
+                }
+            ));
+            self.service_filter.replace(Some(search_filter.clone()));
+            self.root_filter.replace(Some(root_filter.clone()));
+
+            // One sorter for the VMs and for the units within each VM
+            let sorter = CustomSorter::new(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                #[upgrade_or]
+                gtk::Ordering::Equal,
+                move |a, b| match (a.downcast_ref(), b.downcast_ref()) {
+                    (Some(a), Some(b)) => window.imp().sort_order.get().compare(a, b).into(),
+                    _ => gtk::Ordering::Equal,
+                }
+            ));
+            self.service_sorter.replace(Some(sorter.clone()));
+            model.connect_service_changed(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                #[weak]
+                sorter,
+                move |_, _| {
+                    if window.imp().sort_order.get().follows_updates() {
+                        sorter.changed(SorterChange::Different);
+                    }
+                }
+            ));
+
+            let roots = SortListModel::new(
+                Some(FilterListModel::new(
+                    Some(model.clone()),
+                    Some(root_filter.clone()),
+                )),
+                Some(sorter.clone()),
+            );
+            // VMs coming and going change which units are orphans
+            model.connect_items_changed(glib::clone!(
+                #[weak]
+                root_filter,
+                move |_, _, _, _| root_filter.changed(FilterChange::Different)
+            ));
+
+            let tree = TreeListModel::new(
+                roots,
+                false,
+                true,
+                glib::clone!(
+                    #[strong]
+                    model,
+                    move |item| {
+                        let vm_name = item
+                            .downcast_ref::<ServiceGObject>()
+                            .filter(|obj| obj.is_vm())?
+                            .vm_name();
+                        let filter = EveryFilter::new();
+                        filter.append(CustomFilter::typed(move |obj: &ServiceGObject| {
+                            !obj.is_vm() && obj.vm_name() == vm_name
+                        }));
+                        filter.append(search_filter.clone());
+                        let units = FilterListModel::new(Some(model.clone()), Some(filter));
+                        Some(SortListModel::new(Some(units), Some(sorter.clone())).upcast())
+                    }
+                ),
+            );
+
+            let app = self.obj().get_app_ref();
+            let preferences = app.preferences();
+            self.collapsed_vms.replace(preferences.collapsed_vms);
+            self.sort_order.set(preferences.sort_order);
+            tree.connect_items_changed(glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |tree, position, _, added| {
+                    window.imp().track_expansion(tree, position, added);
+                }
+            ));
+            tree
+        }
+
+        /// Remember which VMs the user collapsed, and collapse them again when their rows
+        /// are recreated.
+        fn track_expansion(&self, tree: &TreeListModel, position: u32, added: u32) {
+            let vm_name = |row: &TreeListRow| {
+                row.item()
+                    .and_downcast::<ServiceGObject>()
+                    .as_ref()
+                    .map(ServiceGObject::vm_name)
+            };
+
+            // Expanding or collapsing a row inserts or removes the items right after it
+            if let Some(row) = position.checked_sub(1).and_then(|pos| tree.row(pos))
+                && row.is_expandable()
+                && let Some(vm) = vm_name(&row)
+            {
+                let changed = if row.is_expanded() {
+                    self.collapsed_vms.borrow_mut().remove(&vm)
+                } else {
+                    self.collapsed_vms.borrow_mut().insert(vm)
+                };
+                if changed {
+                    let collapsed = self.collapsed_vms.borrow().clone();
+                    self.obj()
+                        .get_app_ref()
+                        .update_preferences(|prefs| prefs.collapsed_vms = collapsed);
+                }
+            }
+
+            let collapsed = self.collapsed_vms.borrow();
+            let rows: Vec<TreeListRow> = (position..position + added)
+                .filter_map(|pos| tree.row(pos))
+                .filter(|row| {
+                    row.is_expandable()
+                        && row.is_expanded()
+                        && vm_name(row).is_some_and(|vm| collapsed.contains(&vm))
+                })
+                .collect();
+            if !rows.is_empty() {
+                // Not while the tree is still emitting items-changed
+                glib::idle_add_local_once(move || {
+                    for row in rows {
+                        row.set_expanded(false);
+                    }
+                });
+            }
+        }
+
+        pub fn setup_actions(&self) {
+            let initial: &'static str = self.sort_order.get().into();
+            let sort_action = gio::ActionEntry::builder("sort-order")
+                .parameter_type(Some(glib::VariantTy::STRING))
+                .state(initial.to_variant())
+                .activate(|window: &super::ControlPanelGuiWindow, action, parameter| {
+                    let Some(name) = parameter.and_then(glib::Variant::str) else {
+                        return;
+                    };
+                    let Ok(order) = name.parse::<SortOrder>() else {
+                        warn!("Unknown sort order {name}");
+                        return;
+                    };
+                    action.set_state(&name.to_variant());
+                    window.imp().set_sort_order(order);
+                })
+                .build();
+            self.obj().add_action_entries([sort_action]);
+        }
+
+        fn set_sort_order(&self, order: SortOrder) {
+            if self.sort_order.replace(order) == order {
+                return;
+            }
+            if let Some(sorter) = self.service_sorter.borrow().as_ref() {
+                sorter.changed(SorterChange::Different);
+            }
+            self.obj()
+                .get_app_ref()
+                .update_preferences(|prefs| prefs.sort_order = order);
+        }
+
+        fn row_service(item: Option<glib::Object>) -> Option<ServiceGObject> {
+            item.and_downcast::<TreeListRow>()?.item().and_downcast()
+        }
+
         pub fn setup_service_rows(&self, model: &ListModel) {
-            let selection_model =
-                SingleSelection::new(Some(model.clone())).wrap::<ServiceGObject>();
+            let selection_model = SingleSelection::new(Some(model.clone()));
             selection_model.connect_selection_changed(glib::clone!(
                 #[strong(rename_to = window)]
                 self.obj(),
-                #[strong]
-                selection_model,
-                move |_, _, _| {
-                    if let Some(obj) = selection_model.selected_obj() {
+                move |selection_model, _, _| {
+                    if let Some(obj) = Self::row_service(selection_model.selected_item()) {
                         let title = obj.name();
                         let subtitle = obj.details();
                         debug!("Property {title}, {subtitle}");
@@ -138,7 +481,7 @@ mod imp {
                     debug!(
                         "Items changed at position {position}, removed: {removed}, added: {added}"
                     );
-                    if let Some(obj) = selection_model.selected_obj() {
+                    if let Some(obj) = Self::row_service(selection_model.selected_item()) {
                         window.imp().set_vm_details(&obj);
                     } else {
                         debug!("No item selected");
@@ -146,9 +489,77 @@ mod imp {
                 }
             ));
 
-            self.services_list_view.set_model(Some(&*selection_model));
+            self.services_list_view.set_model(Some(&selection_model));
             self.bind_service_settings_box_visibility();
             Self::set_default_selection(&selection_model, model.n_items());
+            self.single_selection.replace(Some(selection_model));
+
+            let multi_selection = MultiSelection::new(Some(model.clone()));
+            let update = glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |_: &MultiSelection, _: u32, _: u32| window.imp().update_batch_status()
+            );
+            multi_selection.connect_selection_changed(update.clone());
+            multi_selection.connect_items_changed(move |model, position, _, added| {
+                update(model, position, added);
+            });
+            self.multi_selection.replace(Some(multi_selection));
+        }
+
+        /// Position of the first row for which `pred` holds.
+        fn find_row(
+            selection: &SingleSelection,
+            pred: impl Fn(&ServiceGObject) -> bool,
+        ) -> Option<u32> {
+            (0..selection.n_items())
+                .find(|&idx| Self::row_service(selection.item(idx)).is_some_and(|obj| pred(&obj)))
+        }
+
+        /// Select the row of `obj`, expanding its VM if needed. Returns false if the row is
+        /// not shown, e.g. because of the search.
+        pub fn select_service(&self, obj: &ServiceGObject) -> bool {
+            let Some(selection) = self.single_selection.borrow().clone() else {
+                return false;
+            };
+            if !obj.is_vm()
+                && let Some(idx) =
+                    Self::find_row(&selection, |vm| vm.is_vm() && vm.vm_name() == obj.vm_name())
+                && let Some(row) = selection.item(idx).and_downcast::<TreeListRow>()
+            {
+                row.set_expanded(true);
+            }
+            let Some(idx) = Self::find_row(&selection, |row| row == obj) else {
+                return false;
+            };
+            selection.set_selected(idx);
+            self.services_list_view
+                .scroll_to(idx, gtk::ListScrollFlags::FOCUS, None);
+            true
+        }
+
+        /// Units checked in selection mode, in list order.
+        pub fn selected_services(&self) -> Vec<ServiceGObject> {
+            let Some(multi) = self.multi_selection.borrow().clone() else {
+                return vec![];
+            };
+            (0..multi.n_items())
+                .filter(|&idx| multi.is_selected(idx))
+                .filter_map(|idx| Self::row_service(multi.item(idx)))
+                .collect()
+        }
+
+        pub fn update_batch_status(&self) {
+            if self.batch_running.get() {
+                return;
+            }
+            let count = self.selected_services().len();
+            self.batch_status_label.set_label(&match count {
+                0 => "No units selected".to_owned(),
+                1 => "1 unit selected".to_owned(),
+                n => format!("{n} units selected"),
+            });
+            self.batch_buttons.set_sensitive(count > 0);
         }
 
         fn bind_service_settings_box_visibility(&self) {
@@ -172,11 +583,27 @@ mod imp {
         }
 
         pub fn setup_factory(&self) {
-            let factory = TypedSignalListItemFactory::<ServiceGObject, ServiceRow>::new();
+            let factory = TypedSignalListItemFactory::<TreeListRow, TreeExpander>::new();
 
-            factory.on_setup(|_| ServiceRow::new());
-            factory.on_bind(move |_, row, obj| row.bind(obj));
-            factory.on_unbind(|_, row| row.unbind());
+            factory.on_setup(|_| {
+                let expander = TreeExpander::new();
+                expander.set_child(Some(&ServiceRow::new()));
+                expander
+            });
+            factory.on_bind(move |_, expander, list_row| {
+                expander.set_list_row(Some(list_row));
+                if let Some(row) = expander.child().and_downcast::<ServiceRow>()
+                    && let Some(obj) = list_row.item().and_downcast::<ServiceGObject>()
+                {
+                    row.bind(&obj);
+                }
+            });
+            factory.on_unbind(|_, expander| {
+                expander.set_list_row(None);
+                if let Some(row) = expander.child().and_downcast::<ServiceRow>() {
+                    row.unbind();
+                }
+            });
 
             // Set the factory of the list view
             self.services_list_view.set_factory(Some(&*factory));
@@ -185,6 +612,59 @@ mod imp {
         fn set_vm_details(&self, obj: &ServiceGObject) {
             self.service_settings_box.bind(obj);
         }
+
+        pub fn setup_connection_status(&self, model: &ServiceModel) {
+            let update = glib::clone!(
+                #[weak(rename_to = window)]
+                self.obj(),
+                move |model: &ServiceModel| window.imp().update_connection_status(model)
+            );
+            model.connect_connection_state_notify(update.clone());
+            model.connect_last_error_notify(update.clone());
+            model.connect_retry_in_notify(update);
+            self.update_connection_status(model);
+        }
+
+        fn update_connection_status(&self, model: &ServiceModel) {
+            let target = model.target_name();
+            let error = model.last_error();
+
+            let (icon, tooltip) = match model.connection_state() {
+                ConnectionState::Connected => (
+                    "network-transmit-receive-symbolic",
+                    format!("Connected to {target}"),
+                ),
+                ConnectionState::Connecting => (
+                    "network-idle-symbolic",
+                    format!("Connecting to {target}..."),
+                ),
+                ConnectionState::Disconnected => (
+                    "network-offline-symbolic",
+                    format!("Not connected to {target}"),
+                ),
+                ConnectionState::Failed => (
+                    "network-error-symbolic",
+                    format!("Connection to {target} failed: {error}\nClick to reconnect"),
+                ),
+            };
+            self.connection_indicator.set_icon_name(icon);
+            self.connection_indicator.set_tooltip_text(Some(&tooltip));
+
+            let title = match (model.connection_state(), model.retry_in()) {
+                (ConnectionState::Connected, _) => None,
+                (ConnectionState::Connecting, _) => Some(format!("Connecting to {target}...")),
+                (_, 0) => Some(format!("Not connected to {target}")),
+                (_, retry_in) => Some(format!(
+                    "Not connected to {target} (retrying in {retry_in} s)"
+                )),
+            };
+            if let Some(title) = title {
+                self.connection_banner_title.set_label(&title);
+                self.connection_banner.set_reveal_child(true);
+            } else {
+                self.connection_banner.set_reveal_child(false);
+            }
+        }
     } //end #[gtk::template_callbacks]
 
     impl ObjectImpl for ControlPanelGuiWindow {
@@ -227,9 +707,14 @@ impl ControlPanelGuiWindow {
         self.connect_close_request(glib::clone!(
             #[strong]
             app,
-            move |_| {
+            move |window| {
                 debug!("Close window request");
-                app.activate_action("quit", None);
+                if app.background() {
+                    // Keep the window, and with it the model, for the next launch
+                    window.set_visible(false);
+                } else {
+                    app.activate_action("quit", None);
+                }
                 glib::Propagation::Stop // Returning Stop allows the window to be destroyed
             }
         ));
@@ -240,8 +725,12 @@ impl ControlPanelGuiWindow {
 
         //get application reference
 
-        self.imp().setup_service_rows(&app.get_model());
+        let model = self.imp().setup_service_tree(&app.service_model());
+        self.imp().setup_search(&app.service_model());
+        self.imp().setup_service_rows(model.upcast_ref());
+        self.imp().setup_actions();
         self.imp().setup_factory();
+        self.imp().setup_connection_status(&app.service_model());
         //vm view by default
         self.imp().vm_view_button.set_active(true);
     }
@@ -277,7 +766,313 @@ impl ControlPanelGuiWindow {
         rx
     }
 
+    /// Run `action` on `object`, asking the user first if the action takes the unit down.
+    pub fn request_control_action(&self, action: ControlAction, object: ServiceGObject) {
+        let app = self.get_app_ref();
+        let is_app_vm = matches!(object.vm_type(), VmType::AppVM);
+        if !action.is_destructive() || (is_app_vm && app.preferences().skip_confirm_app_vms) {
+            app.control_service(action, object);
+            return;
+        }
+
+        let title = object.title();
+        let dependents = if object.is_vm() {
+            app.service_model().units_in_vm(&object.vm_name())
+        } else {
+            vec![]
+        };
+        let body = if dependents.is_empty() {
+            format!("Unsaved work in {title} will be lost.")
+        } else {
+            format!(
+                "These apps and services run in {title} and will be stopped too:\n\n{list}",
+                list = bullet_list(&dependents)
+            )
+        };
+
+        self.confirm_destructive_action(action, &title, &body, is_app_vm, move |app| {
+            app.control_service(action, object.clone());
+        });
+    }
+
+    /// Run `action` on every unit checked in selection mode, one after another.
+    pub fn request_batch_action(&self, action: ControlAction) {
+        let targets = self.imp().selected_services();
+        if targets.is_empty() || self.imp().batch_running.get() {
+            return;
+        }
+
+        let app = self.get_app_ref();
+        let all_app_vms = targets
+            .iter()
+            .all(|obj| matches!(obj.vm_type(), VmType::AppVM));
+        if !action.is_destructive() || (all_app_vms && app.preferences().skip_confirm_app_vms) {
+            self.run_batch_action(action, targets);
+            return;
+        }
+
+        let title = format!("{count} units", count = targets.len());
+        let body = format!(
+            "Unsaved work in these units, and in any apps and services running in them, will \
+             be lost:\n\n{list}",
+            list = bullet_list(&targets)
+        );
+        self.confirm_destructive_action(
+            action,
+            &title,
+            &body,
+            all_app_vms,
+            glib::clone!(
+                #[weak(rename_to = win)]
+                self,
+                move |_| win.run_batch_action(action, targets.clone())
+            ),
+        );
+    }
+
+    /// Ask whether to go ahead with a destructive `action` on `title`, and call `on_confirm`
+    /// if the user agrees. `offer_skip` adds the option to stop asking for app VMs.
+    fn confirm_destructive_action(
+        &self,
+        action: ControlAction,
+        title: &str,
+        body: &str,
+        offer_skip: bool,
+        on_confirm: impl Fn(&ControlPanelGuiApplication) + 'static,
+    ) {
+        let mut verb = action.verb().to_owned();
+        verb[..1].make_ascii_uppercase();
+
+        let dialog = adw::AlertDialog::new(Some(&format!("{verb} {title}?")), Some(body));
+        dialog.add_responses(&[("cancel", "_Cancel"), ("confirm", verb.as_str())]);
+        dialog.set_response_appearance("confirm", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let skip_check =
+            offer_skip.then(|| gtk::CheckButton::with_label("Don't ask again for app VMs"));
+        dialog.set_extra_child(skip_check.as_ref());
+
+        dialog.connect_response(
+            None,
+            glib::clone!(
+                #[weak(rename_to = win)]
+                self,
+                move |_, response| {
+                    if response != "confirm" {
+                        return;
+                    }
+                    let app = win.get_app_ref();
+                    if skip_check.as_ref().is_some_and(CheckButtonExt::is_active) {
+                        // Through the action, so that the menu toggle follows
+                        app.change_action_state("skip-confirm-app-vms", &true.to_variant());
+                    }
+                    on_confirm(&app);
+                }
+            ),
+        );
+        dialog.present(Some(self));
+    }
+
+    /// Show how the certificate the admin service presents differs from the one recorded
+    /// for the connection, and trust it if the user says so.
+    pub fn ask_trust_server_certificate(
+        &self,
+        pinned: &PinnedCertificate,
+        presented: &PinnedCertificate,
+    ) {
+        let dialog = adw::AlertDialog::new(
+            Some("Admin service certificate changed"),
+            Some(
+                "The connection was refused. This is expected after the admin service got a \
+                 new certificate, otherwise something else may be answering in its place.",
+            ),
+        );
+        dialog.add_responses(&[
+            ("cancel", "_Keep Refusing"),
+            ("trust", "_Trust New Certificate"),
+        ]);
+        dialog.set_response_appearance("trust", adw::ResponseAppearance::Destructive);
+        dialog.set_default_response(Some("cancel"));
+        dialog.set_close_response("cancel");
+
+        let grid = gtk::Grid::builder()
+            .column_spacing(12)
+            .row_spacing(6)
+            .build();
+        for (column, heading) in [(1, "Recorded"), (2, "Presented")] {
+            let label = gtk::Label::builder().label(heading).xalign(0.0).build();
+            label.add_css_class("heading");
+            grid.attach(&label, column, 0, 1, 1);
+        }
+        let rows = [
+            ("Fingerprint", &pinned.fingerprint, &presented.fingerprint),
+            ("Subject", &pinned.subject, &presented.subject),
+            ("Issuer", &pinned.issuer, &presented.issuer),
+            ("Valid until", &pinned.not_after, &presented.not_after),
+        ];
+        for (row, (field, old, new)) in (1..).zip(rows) {
+            grid.attach(&gtk::Label::new(Some(field)), 0, row, 1, 1);
+            for (column, value) in [(1, old), (2, new)] {
+                let label = gtk::Label::builder()
+                    .label(value.as_str())
+                    .xalign(0.0)
+                    .wrap(true)
+                    .wrap_mode(gtk::pango::WrapMode::Char)
+                    .max_width_chars(24)
+                    .selectable(true)
+                    .build();
+                if old != new {
+                    label.add_css_class("error");
+                }
+                grid.attach(&label, column, row, 1, 1);
+            }
+        }
+        dialog.set_extra_child(Some(&grid));
+
+        let presented = presented.clone();
+        dialog.connect_response(
+            None,
+            glib::clone!(
+                #[weak(rename_to = win)]
+                self,
+                move |_, response| {
+                    if response == "trust" {
+                        win.get_app_ref()
+                            .trust_server_certificate(presented.clone());
+                    }
+                }
+            ),
+        );
+        dialog.present(Some(self));
+    }
+
+    fn run_batch_action(&self, action: ControlAction, targets: Vec<ServiceGObject>) {
+        glib::spawn_future_local(glib::clone!(
+            #[weak(rename_to = win)]
+            self,
+            async move {
+                let imp = win.imp();
+                let app = win.get_app_ref();
+                let total = targets.len();
+                let mut done = 0;
+                let mut skipped = 0;
+                let mut failed = vec![];
+
+                imp.batch_running.set(true);
+                imp.batch_buttons.set_sensitive(false);
+                for (idx, object) in targets.iter().enumerate() {
+                    let title = object.title();
+                    if !object.is_controllable()
+                        || !action.is_available(object.status())
+                        || object.operation() != ServiceOperation::Idle
+                    {
+                        debug!("Batch {action:?}: skipping {title}");
+                        skipped += 1;
+                        continue;
+                    }
+                    imp.batch_status_label.set_label(&format!(
+                        "{doing} {title} ({n} of {total})...",
+                        doing = action.in_progress(),
+                        n = idx + 1
+                    ));
+                    match app.run_control_action(action, object, Origin::Panel).await {
+                        Ok(()) => done += 1,
+                        Err(e) => {
+                            warn!("{action:?} of {name} failed: {e:#}", name = object.name());
+                            failed.push(title);
+                        }
+                    }
+                }
+                imp.batch_running.set(false);
+                imp.update_batch_status();
+
+                let mut summary = format!(
+                    "{done} of {total} {done_verb}",
+                    done_verb = action.past_tense()
+                );
+                if skipped > 0 {
+                    summary.push_str(&format!(", {skipped} skipped"));
+                }
+                if !failed.is_empty() {
+                    summary.push_str(&format!(", failed: {list}", list = failed.join(", ")));
+                }
+                let toast = adw::Toast::builder()
+                    .title(summary)
+                    .use_markup(false)
+                    .timeout(if failed.is_empty() { 5 } else { 10 })
+                    .build();
+                imp.toast_overlay.add_toast(toast);
+            }
+        ));
+    }
+
+    pub fn show_control_result(
+        &self,
+        action: ControlAction,
+        object: &ServiceGObject,
+        result: &Result<(), anyhow::Error>,
+    ) {
+        let title = object.title();
+        let toast = match result {
+            Ok(()) => adw::Toast::builder()
+                .title(format!("{title} {done}", done = action.past_tense()))
+                .use_markup(false)
+                .build(),
+            Err(e) => {
+                let toast = adw::Toast::builder()
+                    .title(format!(
+                        "Failed to {verb} {title}: {e:#}",
+                        verb = action.verb()
+                    ))
+                    .use_markup(false)
+                    .button_label("Retry")
+                    .timeout(10)
+                    .build();
+                toast.connect_button_clicked(glib::clone!(
+                    #[weak(rename_to = win)]
+                    self,
+                    #[strong]
+                    object,
+                    move |_| win.get_app_ref().control_service(action, object.clone())
+                ));
+                toast
+            }
+        };
+        self.imp().toast_overlay.add_toast(toast);
+    }
+
     //pub API
+    /// Show the page named `page`: `services`, `settings`, or one of the settings pages
+    /// `settings/locale`, `settings/history` and `settings/about`, which may also be given
+    /// without the `settings/` prefix. Returns false if there is no such page.
+    pub fn open_page(&self, page: &str) -> bool {
+        let imp = self.imp();
+        match Page::parse(page) {
+            Page::Services => imp.vm_view_button.set_active(true),
+            Page::Settings(None) => imp.settings_view_button.set_active(true),
+            Page::Settings(Some(name)) => {
+                if !imp.settings_box.show_page(name) {
+                    return false;
+                }
+                imp.settings_view_button.set_active(true);
+            }
+        }
+        true
+    }
+
+    /// Show the services page with the unit named `name`, or the VM named `name`, selected.
+    /// Returns false if there is no such unit, or it is hidden by the search.
+    pub fn select_service(&self, name: &str) -> bool {
+        let Some(obj) = self.get_app_ref().service_model().find_unit(name) else {
+            return false;
+        };
+        let imp = self.imp();
+        imp.vm_view_button.set_active(true);
+        imp.selection_mode_button.set_active(false);
+        imp.select_service(&obj)
+    }
+
     pub fn set_locale_model(&self, model: impl IsA<ListModel>, selected: Option<usize>) {
         self.imp().settings_box.set_locale_model(model, selected);
     }
@@ -286,3 +1081,11 @@ impl ControlPanelGuiWindow {
         self.imp().settings_box.set_timezone_model(model, selected);
     }
 }
+
+fn bullet_list(objects: &[ServiceGObject]) -> String {
+    objects
+        .iter()
+        .map(|obj| format!("• {title}", title = obj.title()))
+        .collect::<Vec<_>>()
+        .join("\n")
+}